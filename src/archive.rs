@@ -0,0 +1,53 @@
+use std::{
+  collections::HashSet,
+  fs::OpenOptions,
+  io::Write,
+  path::{Path, PathBuf},
+  sync::Mutex,
+};
+
+use crate::downloader_error::DownloaderError;
+
+pub struct DownloadArchive {
+  path: PathBuf,
+  state: Mutex<ArchiveState>,
+}
+
+#[derive(Default)]
+struct ArchiveState {
+  recorded: HashSet<String>,
+  in_progress: HashSet<String>,
+}
+
+impl DownloadArchive {
+  pub fn open(path: &Path) -> Result<Self, DownloaderError> {
+    let recorded = match std::fs::read_to_string(path) {
+      Ok(content) => content.lines().map(|line| line.trim()).filter(|line| !line.is_empty()).map(|line| line.to_string()).collect(),
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashSet::new(),
+      Err(_) => return Err(DownloaderError::IOError),
+    };
+
+    Ok(DownloadArchive { path: path.to_path_buf(), state: Mutex::new(ArchiveState { recorded, in_progress: HashSet::new() }) })
+  }
+
+  //returns false if the key is already archived or being downloaded by another job
+  pub fn begin(&self, key: &str) -> bool {
+    let mut state = self.state.lock().unwrap();
+    if state.recorded.contains(key) || state.in_progress.contains(key) {
+      return false;
+    }
+
+    state.in_progress.insert(key.to_string())
+  }
+
+  pub fn finish(&self, key: &str, succeeded: bool) -> Result<(), DownloaderError> {
+    let mut state = self.state.lock().unwrap();
+    state.in_progress.remove(key);
+    if !succeeded || !state.recorded.insert(key.to_string()) {
+      return Ok(());
+    }
+
+    let mut file = OpenOptions::new().create(true).append(true).open(&self.path).map_err(|_| DownloaderError::IOError)?;
+    writeln!(file, "{key}").map_err(|_| DownloaderError::IOError)
+  }
+}
@@ -0,0 +1,211 @@
+use futures::future::join_all;
+use reqwest::Url;
+use std::{
+  collections::HashSet,
+  fmt::Display,
+  io::{self, Read},
+};
+use tracing::info;
+
+use crate::{
+  downloader::{Downloader, PreferredResolution},
+  downloader_error::DownloaderError,
+};
+
+const TRACKING_PARAMS: [&str; 17] = [
+  "s",
+  "t",
+  "ref_src",
+  "ref_url",
+  "is_from_webapp",
+  "sender_device",
+  "sender_web_id",
+  "web_id",
+  "share_app_id",
+  "share_item_id",
+  "share_link_id",
+  "u_code",
+  "user_id",
+  "_r",
+  "_t",
+  "_d",
+  "fbclid",
+];
+
+pub struct BatchReport {
+  pub succeeded: Vec<(String, Vec<String>)>,
+  pub failed: Vec<(String, String)>,
+  pub archived: Vec<String>,
+  pub duplicates: usize,
+}
+
+pub fn read_urls(source: &str) -> io::Result<Vec<String>> {
+  let content = if source == "-" {
+    let mut buffer = String::new();
+    io::stdin().read_to_string(&mut buffer)?;
+    buffer
+  } else {
+    std::fs::read_to_string(source)?
+  };
+
+  let urls = content
+    .lines()
+    .map(|line| line.trim())
+    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+    .map(|line| line.to_string())
+    .collect();
+
+  Ok(urls)
+}
+
+pub fn normalize_url(url: &str) -> String {
+  let Ok(mut parsed) = Url::parse(url.trim()) else {
+    return url.trim().to_string();
+  };
+
+  let canonical_host = match parsed.host_str().unwrap_or_default() {
+    "twitter.com" | "www.twitter.com" | "mobile.twitter.com" | "www.x.com" | "mobile.x.com" => Some("x.com"),
+    "tiktok.com" | "m.tiktok.com" => Some("www.tiktok.com"),
+    _ => None,
+  };
+  if let Some(host) = canonical_host {
+    let _ = parsed.set_host(Some(host));
+  }
+  let _ = parsed.set_scheme("https");
+
+  let kept_params: Vec<(String, String)> = parsed
+    .query_pairs()
+    .filter(|(key, _)| !TRACKING_PARAMS.contains(&key.as_ref()) && !key.starts_with("utm_"))
+    .map(|(key, value)| (key.into_owned(), value.into_owned()))
+    .collect();
+  if kept_params.is_empty() {
+    parsed.set_query(None);
+  } else {
+    parsed.query_pairs_mut().clear().extend_pairs(kept_params);
+  }
+  parsed.set_fragment(None);
+
+  let trimmed_path = parsed.path().trim_end_matches('/').to_string();
+  if !trimmed_path.is_empty() {
+    parsed.set_path(&trimmed_path);
+  }
+
+  parsed.to_string()
+}
+
+pub fn deduplicate(urls: Vec<String>) -> Vec<String> {
+  let mut seen = HashSet::new();
+  let mut unique = vec![];
+  for url in urls {
+    let normalized = normalize_url(&url);
+    if seen.insert(normalized.clone()) {
+      unique.push(normalized);
+    }
+  }
+
+  unique
+}
+
+pub async fn run(downloader: &Downloader, urls: Vec<String>, preferred_resolution: Option<PreferredResolution>) -> BatchReport {
+  let total = urls.len();
+  let unique_urls = deduplicate(urls);
+  let mut report = BatchReport { succeeded: vec![], failed: vec![], archived: vec![], duplicates: total - unique_urls.len() };
+  info!("Starting batch of {} urls ({} duplicates skipped)", unique_urls.len(), report.duplicates);
+
+  //the downloader limits how many of these run at the same time
+  let downloads = unique_urls.iter().map(|url| downloader.download(url, preferred_resolution.clone()));
+  let results = join_all(downloads).await;
+
+  for (url, result) in unique_urls.into_iter().zip(results) {
+    match result {
+      Ok(output) => report.succeeded.push((url, output)),
+      Err(DownloaderError::AlreadyDownloadedError) => report.archived.push(url),
+      Err(e) => report.failed.push((url, e.to_string())),
+    }
+  }
+
+  report
+}
+
+impl Display for BatchReport {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    writeln!(
+      f,
+      "Batch finished: {} succeeded, {} failed, {} already archived, {} duplicates skipped",
+      self.succeeded.len(),
+      self.failed.len(),
+      self.archived.len(),
+      self.duplicates
+    )?;
+    for (url, output) in &self.succeeded {
+      writeln!(f, "  [ok] {url} -> {}", output.join(", "))?;
+    }
+    for (url, error) in &self.failed {
+      writeln!(f, "  [failed] {url}: {error}")?;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn normalize_url_collapses_twitter_hosts() {
+    let canonical = "https://x.com/user/status/1790000000000000001";
+    for url in [
+      "https://twitter.com/user/status/1790000000000000001",
+      "https://www.twitter.com/user/status/1790000000000000001",
+      "https://mobile.twitter.com/user/status/1790000000000000001",
+      "https://mobile.x.com/user/status/1790000000000000001",
+      "https://www.x.com/user/status/1790000000000000001",
+      "http://x.com/user/status/1790000000000000001",
+    ] {
+      assert_eq!(normalize_url(url), canonical, "{url}");
+    }
+  }
+
+  #[test]
+  fn normalize_url_collapses_tiktok_hosts() {
+    assert_eq!(normalize_url("https://tiktok.com/@user/video/7234567890123456789"), "https://www.tiktok.com/@user/video/7234567890123456789");
+  }
+
+  #[test]
+  fn normalize_url_strips_tracking_params() {
+    assert_eq!(normalize_url("https://x.com/user/status/1?s=20&t=abc"), "https://x.com/user/status/1");
+    assert_eq!(
+      normalize_url("https://www.tiktok.com/@user/video/2?utm_source=copy&utm_campaign=share&is_from_webapp=1"),
+      "https://www.tiktok.com/@user/video/2"
+    );
+  }
+
+  #[test]
+  fn normalize_url_keeps_other_params() {
+    assert_eq!(normalize_url("https://x.com/user/status/1?s=20&lang=en"), "https://x.com/user/status/1?lang=en");
+  }
+
+  #[test]
+  fn normalize_url_drops_trailing_slashes_and_fragments() {
+    assert_eq!(normalize_url("https://x.com/user/status/1/#m"), "https://x.com/user/status/1");
+    assert_eq!(normalize_url("https://www.tiktok.com/@user/video/2//"), "https://www.tiktok.com/@user/video/2");
+    assert_eq!(normalize_url("https://x.com/"), "https://x.com/");
+  }
+
+  #[test]
+  fn normalize_url_passes_non_urls_through() {
+    assert_eq!(normalize_url("  not a url "), "not a url");
+    assert_eq!(normalize_url("x.com/user/status/1"), "x.com/user/status/1");
+  }
+
+  #[test]
+  fn deduplicate_keeps_the_first_of_equivalent_urls() {
+    let urls = vec![
+      "https://twitter.com/user/status/1?s=20".to_string(),
+      "https://mobile.x.com/user/status/1/".to_string(),
+      "https://x.com/user/status/1#reply".to_string(),
+      "https://x.com/user/status/2".to_string(),
+    ];
+    assert_eq!(deduplicate(urls), ["https://x.com/user/status/1", "https://x.com/user/status/2"]);
+  }
+}
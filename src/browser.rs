@@ -0,0 +1,268 @@
+use base64::{prelude::BASE64_STANDARD, Engine};
+use headless_chrome::{
+  browser::transport::{SessionId, Transport},
+  protocol::cdp::{
+    Fetch::{RequestId, TakeResponseBodyAsStream},
+    Target::{CreateTarget, DisposeBrowserContext},
+    IO::{Close, Read},
+  },
+  Browser, LaunchOptions, Tab,
+};
+use std::{
+  ffi::OsStr,
+  ops::Deref,
+  path::PathBuf,
+  sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+  },
+  time::Duration,
+};
+use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore};
+use tracing::{info, warn};
+
+use crate::{cookies::Cookie, downloader_error::DownloaderError};
+
+const BROWSER_IDLE_TIMEOUT: Duration = Duration::from_secs(1e7 as u64);
+const RESPONSE_CHUNK_SIZE: u32 = 1 << 20;
+
+pub struct BrowserOptions {
+  pub ws_url: Option<String>,
+  pub path: Option<PathBuf>,
+  pub headless: bool,
+  pub args: Vec<String>,
+  pub user_data_dir: Option<PathBuf>,
+  pub max_tabs: usize,
+  pub cookies: Vec<Cookie>,
+}
+
+impl Default for BrowserOptions {
+  fn default() -> Self {
+    Self { ws_url: None, path: None, headless: true, args: vec![], user_data_dir: None, max_tabs: 4, cookies: vec![] }
+  }
+}
+
+//chrome is only launched once an extractor actually asks for it
+pub struct BrowserManager {
+  options: BrowserOptions,
+  browser: Mutex<Option<Arc<Browser>>>,
+  browser_pid: Arc<AtomicU32>,
+  tab_slots: Arc<Semaphore>,
+  idle_contexts: Arc<std::sync::Mutex<IdleContexts>>,
+}
+
+//contexts created by a previous browser instance are dropped by bumping the generation
+#[derive(Default)]
+struct IdleContexts {
+  generation: u64,
+  ids: Vec<String>,
+  created: Vec<String>,
+}
+
+pub struct PooledTab {
+  tab: Arc<Tab>,
+  context_id: Option<String>,
+  generation: u64,
+  idle_contexts: Arc<std::sync::Mutex<IdleContexts>>,
+  _tab_slot: OwnedSemaphorePermit,
+}
+
+impl BrowserManager {
+  pub fn new(options: BrowserOptions) -> Self {
+    let browser_pid = Arc::new(AtomicU32::new(0));
+    let pid_clone = browser_pid.clone();
+    let _ = ctrlc::set_handler(move || {
+      let pid = pid_clone.load(Ordering::SeqCst);
+      if pid != 0 {
+        kill_process_tree(pid);
+        info!("Killed browser process (PID: {})", pid);
+      }
+      info!("Shutting down...");
+      std::process::exit(0);
+    });
+
+    let tab_slots = Arc::new(Semaphore::new(options.max_tabs.max(1)));
+    Self { options, browser: Mutex::new(None), browser_pid, tab_slots, idle_contexts: Arc::new(std::sync::Mutex::new(IdleContexts::default())) }
+  }
+
+  pub async fn get(&self) -> Result<Arc<Browser>, DownloaderError> {
+    let mut browser = self.browser.lock().await;
+    if let Some(existing) = browser.as_ref() {
+      if existing.get_version().is_ok() {
+        return Ok(existing.clone());
+      }
+
+      warn!("Browser stopped responding, relaunching");
+      let created = {
+        let mut idle_contexts = self.idle_contexts.lock().unwrap();
+        idle_contexts.generation += 1;
+        idle_contexts.ids.clear();
+        std::mem::take(&mut idle_contexts.created)
+      };
+      dispose_contexts(existing, &created);
+      *browser = None;
+    }
+
+    let launched = Arc::new(self.launch()?);
+    *browser = Some(launched.clone());
+    Ok(launched)
+  }
+
+  //waits for a free slot, then opens a tab in an isolated browser context
+  //a profile or an attached browser keeps its logins in the default context, so its tabs are opened there instead
+  pub async fn open_tab(&self) -> Result<PooledTab, DownloaderError> {
+    let tab_slot = self.tab_slots.clone().acquire_owned().await.map_err(|e| DownloaderError::BrowserError(e.to_string()))?;
+    let browser = self.get().await?;
+
+    let (context_id, generation) = if self.options.user_data_dir.is_some() || self.options.ws_url.is_some() {
+      (None, self.idle_contexts.lock().unwrap().generation)
+    } else {
+      let pooled_context = {
+        let mut idle_contexts = self.idle_contexts.lock().unwrap();
+        idle_contexts.ids.pop().map(|id| (id, idle_contexts.generation))
+      };
+      match pooled_context {
+        Some((context_id, generation)) => (Some(context_id), generation),
+        None => {
+          let context_id = browser.new_context()?.get_id().to_string();
+          let mut idle_contexts = self.idle_contexts.lock().unwrap();
+          idle_contexts.created.push(context_id.clone());
+          (Some(context_id), idle_contexts.generation)
+        }
+      }
+    };
+
+    let tab = browser.new_tab_with_options(get_tab_create_target(context_id.as_deref()))?;
+    if !self.options.cookies.is_empty() {
+      tab.set_cookies(self.options.cookies.iter().map(Cookie::to_cookie_param).collect())?;
+    }
+    Ok(PooledTab { tab, context_id, generation, idle_contexts: self.idle_contexts.clone(), _tab_slot: tab_slot })
+  }
+
+  fn launch(&self) -> Result<Browser, DownloaderError> {
+    if let Some(ws_url) = &self.options.ws_url {
+      info!("Connecting to browser at {ws_url}");
+      return Browser::connect_with_timeout(ws_url.clone(), BROWSER_IDLE_TIMEOUT).map_err(|e| DownloaderError::BrowserError(e.to_string()));
+    }
+
+    //a profile directory is only useful if its cookies and storage are not hidden behind incognito
+    let mut args = vec![OsStr::new("--mute-audio")];
+    if self.options.user_data_dir.is_none() {
+      args.push(OsStr::new("--incognito"));
+    }
+    args.extend(self.options.args.iter().map(OsStr::new));
+
+    info!("Launching browser");
+    let browser = Browser::new(LaunchOptions {
+      headless: self.options.headless,
+      path: self.options.path.clone(),
+      user_data_dir: self.options.user_data_dir.clone(),
+      idle_browser_timeout: BROWSER_IDLE_TIMEOUT,
+      args,
+      ..Default::default()
+    })
+    .map_err(|e| DownloaderError::BrowserError(e.to_string()))?;
+
+    if let Some(pid) = browser.get_process_id() {
+      self.browser_pid.store(pid, Ordering::SeqCst);
+    }
+
+    Ok(browser)
+  }
+}
+
+impl Deref for PooledTab {
+  type Target = Tab;
+
+  fn deref(&self) -> &Self::Target {
+    &self.tab
+  }
+}
+
+impl Drop for PooledTab {
+  fn drop(&mut self) {
+    let _ = self.tab.close(false);
+
+    let mut idle_contexts = self.idle_contexts.lock().unwrap();
+    if let Some(context_id) = self.context_id.take().filter(|_| idle_contexts.generation == self.generation) {
+      idle_contexts.ids.push(context_id);
+    }
+  }
+}
+
+impl Drop for BrowserManager {
+  fn drop(&mut self) {
+    if let Some(browser) = self.browser.get_mut() {
+      let created = std::mem::take(&mut self.idle_contexts.lock().unwrap().created);
+      dispose_contexts(browser, &created);
+    }
+  }
+}
+
+//the browser only exposes its own connection to tabs, so the contexts are disposed through one of them
+fn dispose_contexts(browser: &Browser, context_ids: &[String]) {
+  if context_ids.is_empty() {
+    return;
+  }
+
+  let Ok(tab) = browser.new_tab() else {
+    return;
+  };
+  for context_id in context_ids {
+    let _ = tab.call_method(DisposeBrowserContext { browser_context_id: context_id.clone() });
+  }
+  let _ = tab.close(false);
+}
+
+//takes the body of a request paused at the response stage, the request has to be failed or fulfilled afterwards
+pub fn take_response_body(transport: &Transport, session_id: SessionId, request_id: RequestId) -> Result<Vec<u8>, DownloaderError> {
+  let stream = transport.call_method_on_target(session_id.clone(), TakeResponseBodyAsStream { request_id })?.stream;
+
+  let mut body = vec![];
+  loop {
+    let chunk =
+      transport.call_method_on_target(session_id.clone(), Read { handle: stream.clone(), offset: None, size: Some(RESPONSE_CHUNK_SIZE) })?;
+    if chunk.base_64_encoded.unwrap_or(false) {
+      body.extend(BASE64_STANDARD.decode(chunk.data).map_err(|e| DownloaderError::OtherError(e.to_string()))?);
+    } else {
+      body.extend(chunk.data.into_bytes());
+    }
+
+    if chunk.eof {
+      break;
+    }
+  }
+  let _ = transport.call_method_on_target(session_id, Close { handle: stream });
+
+  Ok(body)
+}
+
+fn get_tab_create_target(context_id: Option<&str>) -> CreateTarget {
+  CreateTarget {
+    url: "about:blank".to_string(),
+    width: None,
+    height: None,
+    browser_context_id: context_id.map(|context_id| context_id.to_string()),
+    enable_begin_frame_control: None,
+    new_window: Some(true),
+    background: Some(true),
+    left: None,
+    top: None,
+    window_state: None,
+    for_tab: None,
+    hidden: None,
+  }
+}
+
+fn kill_process_tree(pid: u32) {
+  use std::process::Command;
+
+  #[cfg(target_os = "windows")]
+  {
+    let _ = Command::new("taskkill").args(["/PID", &pid.to_string(), "/T", "/F"]).output();
+  }
+  #[cfg(target_os = "linux")]
+  {
+    let _ = Command::new("kill").args(["-9", &format!("-{}", pid)]).output();
+  }
+}
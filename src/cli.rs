@@ -0,0 +1,134 @@
+use clap::{builder::RangedU64ValueParser, Args, Parser, Subcommand};
+use clap_complete::Shell;
+use std::path::PathBuf;
+
+use crate::{
+  dates,
+  downloader::{GifFormat, PreferredResolution, SoundFormat, VideoCodec},
+};
+
+#[derive(Parser)]
+#[command(name = "vid-downloader", version, about = "Download videos from different media platforms", arg_required_else_help = true)]
+pub struct Cli {
+  /// Config file to use instead of the default location
+  #[arg(long, global = true, value_name = "FILE")]
+  pub config: Option<PathBuf>,
+  /// Netscape cookies.txt file used by the browser and for media requests
+  #[arg(long, global = true, value_name = "FILE")]
+  pub cookies: Option<PathBuf>,
+  #[command(subcommand)]
+  pub command: Command,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+  /// Download a single video
+  Download {
+    url: String,
+    #[command(flatten)]
+    options: DownloadArgs,
+  },
+  /// List the formats available for a video
+  Formats {
+    url: String,
+    #[command(flatten)]
+    browser: BrowserArgs,
+  },
+  /// Keep handling links typed into stdin (type exit to quit)
+  Serve {
+    #[command(flatten)]
+    options: DownloadArgs,
+  },
+  /// Download every link listed in a file, one per line (- for stdin)
+  Batch {
+    file: String,
+    #[command(flatten)]
+    options: DownloadArgs,
+  },
+  /// Print shell completions to stdout
+  Completions { shell: Shell },
+  /// Print the man page to stdout
+  Man,
+}
+
+#[derive(Args, Default)]
+pub struct DownloadArgs {
+  /// Preferred resolution
+  #[arg(short, long, value_enum)]
+  pub resolution: Option<PreferredResolution>,
+  /// Skip posts recorded in this file and record new downloads
+  #[arg(long, value_name = "FILE")]
+  pub download_archive: Option<PathBuf>,
+  /// Output path template, e.g. "{platform}/{id}.{ext}"
+  #[arg(short, long, value_name = "TEMPLATE")]
+  pub output: Option<String>,
+  /// Maximum number of simultaneous downloads
+  #[arg(long, value_name = "N")]
+  pub concurrency: Option<usize>,
+  /// Path to the ffmpeg executable
+  #[arg(long, value_name = "PATH")]
+  pub ffmpeg: Option<PathBuf>,
+  /// Capture media from the browser's response instead of requesting it again
+  #[arg(long)]
+  pub capture_response: bool,
+  /// Also download images from posts at their original quality
+  #[arg(long)]
+  pub include_images: bool,
+  /// Video codec to prefer when a post offers several
+  #[arg(long, value_enum)]
+  pub codec: Option<VideoCodec>,
+  /// Format to save animated GIFs in, converting from MP4 with ffmpeg
+  #[arg(long, value_enum)]
+  pub gif_format: Option<GifFormat>,
+  /// Write post metadata to a .info.json file next to each download
+  #[arg(long)]
+  pub write_info_json: bool,
+  /// Download at most this many posts from a profile or other listing
+  #[arg(long, value_name = "N", value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+  pub max_count: Option<usize>,
+  /// Only download posts from a listing published on or after this date (YYYYMMDD)
+  #[arg(long, value_name = "DATE", value_parser = dates::parse_date)]
+  pub date_after: Option<i64>,
+  /// Only download posts from a listing published on or before this date (YYYYMMDD)
+  #[arg(long, value_name = "DATE", value_parser = dates::parse_date)]
+  pub date_before: Option<i64>,
+  /// Also download the rest of the author's thread the post belongs to
+  #[arg(long)]
+  pub thread: bool,
+  /// Like --thread, also downloading tweets quoted in the thread
+  #[arg(long)]
+  pub thread_quotes: bool,
+  /// Also render photo slideshows into an MP4 with their sound
+  #[arg(long)]
+  pub slideshow_video: bool,
+  /// Download only the sound of TikTok posts and sound pages, tagged with its title and author
+  #[arg(long, value_enum, value_name = "FORMAT")]
+  pub sound_only: Option<SoundFormat>,
+  /// Log every candidate media request of a page when none of them matched the video
+  #[arg(long)]
+  pub log_media_requests: bool,
+  #[command(flatten)]
+  pub browser: BrowserArgs,
+}
+
+#[derive(Args, Default)]
+pub struct BrowserArgs {
+  /// Attach to an existing browser through its DevTools websocket url instead of launching one
+  #[arg(long, value_name = "URL")]
+  pub browser_ws: Option<String>,
+  /// Path to the Chrome executable
+  #[arg(long, value_name = "PATH")]
+  pub chrome_path: Option<PathBuf>,
+  /// Extra argument passed to Chrome (can be repeated)
+  #[arg(long, value_name = "ARG", allow_hyphen_values = true)]
+  pub browser_arg: Vec<String>,
+  /// Show the browser window instead of running headless
+  #[arg(long)]
+  pub headful: bool,
+  /// Chrome profile directory to use
+  #[arg(long, value_name = "DIR")]
+  pub user_data_dir: Option<PathBuf>,
+  /// Maximum number of browser tabs open at the same time
+  #[arg(long, value_name = "N")]
+  pub max_tabs: Option<usize>,
+}
@@ -0,0 +1,70 @@
+use serde::Deserialize;
+use std::{
+  collections::HashMap,
+  path::{Path, PathBuf},
+};
+
+use crate::{
+  downloader::{GifFormat, PreferredResolution, SoundFormat, VideoCodec},
+  downloader_error::DownloaderError,
+};
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+  pub output_template: Option<String>,
+  pub resolution: Option<PreferredResolution>,
+  pub concurrency: Option<usize>,
+  pub ffmpeg_path: Option<PathBuf>,
+  pub download_archive: Option<PathBuf>,
+  pub cookies_file: Option<PathBuf>,
+  pub capture_response: Option<bool>,
+  pub include_images: Option<bool>,
+  pub gif_format: Option<GifFormat>,
+  pub codec: Option<VideoCodec>,
+  pub write_info_json: Option<bool>,
+  pub slideshow_video: Option<bool>,
+  pub sound_only: Option<SoundFormat>,
+  pub log_media_requests: Option<bool>,
+  pub browser: BrowserConfig,
+  pub platforms: HashMap<String, PlatformConfig>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct BrowserConfig {
+  pub ws_url: Option<String>,
+  pub path: Option<PathBuf>,
+  pub headless: Option<bool>,
+  pub args: Vec<String>,
+  pub user_data_dir: Option<PathBuf>,
+  pub max_tabs: Option<usize>,
+}
+
+#[derive(Deserialize, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct PlatformConfig {
+  pub headers: HashMap<String, String>,
+  pub cookies: Option<String>,
+  pub timeout_secs: Option<u64>,
+}
+
+impl Config {
+  //explicit paths must exist, the default location is optional
+  pub fn load(path: Option<&Path>) -> Result<Self, DownloaderError> {
+    let path = match path {
+      Some(path) => path.to_path_buf(),
+      None => match Self::default_path() {
+        Some(path) if path.exists() => path,
+        _ => return Ok(Config::default()),
+      },
+    };
+
+    let content = std::fs::read_to_string(&path).map_err(|_| DownloaderError::IOError)?;
+    toml::from_str(&content).map_err(|e| DownloaderError::ConfigError(format!("{}: {}", path.display(), e.message())))
+  }
+
+  pub fn default_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("vid-downloader").join("config.toml"))
+  }
+}
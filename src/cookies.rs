@@ -0,0 +1,215 @@
+use headless_chrome::protocol::cdp::Network::{self, CookieParam};
+use reqwest::{cookie::Jar, Url};
+use std::path::Path;
+use tracing::warn;
+
+use crate::downloader_error::DownloaderError;
+
+const HTTP_ONLY_PREFIX: &str = "#HttpOnly_";
+
+#[derive(Clone)]
+pub struct Cookie {
+  pub domain: String,
+  pub include_subdomains: bool,
+  pub path: String,
+  pub secure: bool,
+  pub http_only: bool,
+  pub expires: i64,
+  pub name: String,
+  pub value: String,
+}
+
+impl Cookie {
+  //host-only cookies are set through their url, a domain would also send them to subdomains
+  pub fn to_cookie_param(&self) -> CookieParam {
+    let (url, domain) = match self.include_subdomains {
+      true => (None, Some(self.domain.clone())),
+      false => (self.origin().map(|origin| origin.to_string()), None),
+    };
+    CookieParam {
+      name: self.name.clone(),
+      value: self.value.clone(),
+      url,
+      domain,
+      path: Some(self.path.clone()),
+      secure: Some(self.secure),
+      http_only: Some(self.http_only),
+      same_site: None,
+      expires: (self.expires > 0).then_some(self.expires as f64),
+      priority: None,
+      same_party: None,
+      source_scheme: None,
+      source_port: None,
+      partition_key: None,
+    }
+  }
+
+  fn origin(&self) -> Option<Url> {
+    let scheme = if self.secure { "https" } else { "http" };
+    Url::parse(&format!("{scheme}://{}{}", self.domain.trim_start_matches('.'), self.path)).ok()
+  }
+}
+
+pub fn load(path: &Path) -> Result<Vec<Cookie>, DownloaderError> {
+  let content = std::fs::read_to_string(path).map_err(|_| DownloaderError::IOError)?;
+  parse(&content)
+}
+
+//netscape format: domain, include subdomains, path, secure, expiry, name, value separated by tabs
+fn parse(content: &str) -> Result<Vec<Cookie>, DownloaderError> {
+  let mut cookies = vec![];
+  for (index, line) in content.lines().enumerate() {
+    let (line, http_only) = match line.strip_prefix(HTTP_ONLY_PREFIX) {
+      Some(stripped) => (stripped, true),
+      None => (line, false),
+    };
+    if line.trim().is_empty() || line.starts_with('#') {
+      continue;
+    }
+
+    let fields: Vec<&str> = line.split('\t').collect();
+    //some exporters leave out the value of empty cookies entirely
+    if fields.len() == 6 {
+      warn!("Skipping line {} of the cookies file, the cookie has no value", index + 1);
+      continue;
+    }
+    let [domain, include_subdomains, path, secure, expires, name, value] = fields[..] else {
+      return Err(DownloaderError::CookiesError(format!("line {} is not a valid cookies.txt entry", index + 1)));
+    };
+
+    cookies.push(Cookie {
+      domain: domain.to_string(),
+      include_subdomains: include_subdomains.eq_ignore_ascii_case("TRUE"),
+      path: path.to_string(),
+      secure: secure.eq_ignore_ascii_case("TRUE"),
+      http_only,
+      expires: expires.parse().unwrap_or(0),
+      name: name.to_string(),
+      value: value.trim_end_matches('\r').to_string(),
+    });
+  }
+
+  Ok(cookies)
+}
+
+//cookies read from a browser keep their scope, a leading dot marks them as shared with subdomains
+pub fn add_browser_cookie(jar: &Jar, cookie: &Network::Cookie) {
+  let Ok(origin) = Url::parse(&format!("https://{}{}", cookie.domain.trim_start_matches('.'), cookie.path)) else {
+    return;
+  };
+
+  let mut cookie_str = format!("{}={}; Path={}", cookie.name, cookie.value, cookie.path);
+  if cookie.domain.starts_with('.') {
+    cookie_str.push_str(&format!("; Domain={}", cookie.domain));
+  }
+  jar.add_cookie_str(&cookie_str, &origin);
+}
+
+//a cookie header seen on a browser request only tells which cookies were sent to that url
+pub fn add_header_cookies(jar: &Jar, header: &str, url: &str) {
+  let Ok(url) = Url::parse(url) else {
+    return;
+  };
+
+  for cookie in header.split(';').map(str::trim).filter(|cookie| cookie.contains('=')) {
+    jar.add_cookie_str(cookie, &url);
+  }
+}
+
+//cookies configured for a platform are sent to every subdomain of its sites
+pub fn add_domain_cookies(jar: &Jar, header: &str, domain: &str) {
+  let Ok(url) = Url::parse(&format!("https://{domain}/")) else {
+    return;
+  };
+
+  for cookie in header.split(';').map(str::trim).filter(|cookie| cookie.contains('=')) {
+    jar.add_cookie_str(&format!("{cookie}; Domain={domain}; Path=/"), &url);
+  }
+}
+
+pub fn to_jar(cookies: &[Cookie]) -> Jar {
+  let jar = Jar::default();
+  for cookie in cookies {
+    let Some(origin) = cookie.origin() else {
+      continue;
+    };
+
+    let mut cookie_str = format!("{}={}; Path={}", cookie.name, cookie.value, cookie.path);
+    if cookie.include_subdomains {
+      cookie_str.push_str(&format!("; Domain={}", cookie.domain));
+    }
+    if cookie.secure {
+      cookie_str.push_str("; Secure");
+    }
+    jar.add_cookie_str(&cookie_str, &origin);
+  }
+
+  jar
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use reqwest::cookie::CookieStore;
+
+  const COOKIES_TXT: &str = "# Netscape HTTP Cookie File\n\
+    \n\
+    .x.com\tTRUE\t/\tTRUE\t1893456000\tauth_token\tsecret\n\
+    #HttpOnly_www.tiktok.com\tFALSE\t/\tTRUE\t0\tsessionid\tabc\r\n\
+    .tiktok.com\tTRUE\t/\tFALSE\t0\tempty\n";
+
+  fn cookie_header(jar: &Jar, url: &str) -> String {
+    jar.cookies(&Url::parse(url).unwrap()).map(|header| header.to_str().unwrap().to_string()).unwrap_or_default()
+  }
+
+  #[test]
+  fn parse_reads_entries_and_skips_comments() {
+    let cookies = parse(COOKIES_TXT).ok().expect("valid cookies file");
+    assert_eq!(cookies.len(), 2);
+
+    let auth_token = &cookies[0];
+    assert_eq!((auth_token.domain.as_str(), auth_token.name.as_str(), auth_token.value.as_str()), (".x.com", "auth_token", "secret"));
+    assert!(auth_token.include_subdomains && auth_token.secure && !auth_token.http_only);
+    assert_eq!(auth_token.expires, 1893456000);
+  }
+
+  #[test]
+  fn parse_marks_http_only_entries() {
+    let cookies = parse(COOKIES_TXT).ok().expect("valid cookies file");
+    let session = &cookies[1];
+    assert_eq!((session.domain.as_str(), session.value.as_str()), ("www.tiktok.com", "abc"));
+    assert!(session.http_only && !session.include_subdomains);
+  }
+
+  #[test]
+  fn parse_skips_entries_without_a_value() {
+    let cookies = parse(COOKIES_TXT).ok().expect("valid cookies file");
+    assert!(cookies.iter().all(|cookie| cookie.name != "empty"));
+  }
+
+  #[test]
+  fn parse_rejects_invalid_entries() {
+    let result = parse("# Netscape HTTP Cookie File\nx.com\tTRUE\t/\n");
+    assert!(matches!(result, Err(DownloaderError::CookiesError(message)) if message.contains("line 2")));
+  }
+
+  #[test]
+  fn to_cookie_param_scopes_host_only_cookies_by_url() {
+    let cookies = parse(COOKIES_TXT).ok().expect("valid cookies file");
+
+    let shared = cookies[0].to_cookie_param();
+    assert_eq!((shared.domain.as_deref(), shared.url.as_deref()), (Some(".x.com"), None));
+
+    let host_only = cookies[1].to_cookie_param();
+    assert_eq!((host_only.domain.as_deref(), host_only.url.as_deref()), (None, Some("https://www.tiktok.com/")));
+  }
+
+  #[test]
+  fn to_jar_sends_host_only_cookies_to_their_host_only() {
+    let jar = to_jar(&parse(COOKIES_TXT).ok().expect("valid cookies file"));
+
+    assert_eq!(cookie_header(&jar, "https://api.x.com/graphql"), "auth_token=secret");
+    assert_eq!(cookie_header(&jar, "https://www.tiktok.com/@user"), "sessionid=abc");
+    assert_eq!(cookie_header(&jar, "https://m.tiktok.com/v/1.html"), "");
+  }
+}
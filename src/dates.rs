@@ -0,0 +1,48 @@
+pub const SECONDS_PER_DAY: i64 = 86400;
+
+//accepts YYYYMMDD or YYYY-MM-DD and returns the unix timestamp of midnight utc
+pub fn parse_date(value: &str) -> Result<i64, String> {
+  let digits: String = value.chars().filter(|c| *c != '-').collect();
+  let invalid_date = || format!("invalid date {value}, expected YYYYMMDD");
+  if digits.len() != 8 || !digits.chars().all(|c| c.is_ascii_digit()) {
+    return Err(invalid_date());
+  }
+
+  let year: i64 = digits[..4].parse().map_err(|_| invalid_date())?;
+  let month: i64 = digits[4..6].parse().map_err(|_| invalid_date())?;
+  let day: i64 = digits[6..].parse().map_err(|_| invalid_date())?;
+  if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+    return Err(invalid_date());
+  }
+
+  Ok(days_from_civil(year, month, day) * SECONDS_PER_DAY)
+}
+
+//days since 1970-01-01 for a proleptic gregorian date
+pub fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+  let year = if month <= 2 { year - 1 } else { year };
+  let era = year.div_euclid(400);
+  let year_of_era = year - era * 400;
+  let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+  let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+  era * 146097 + day_of_era - 719468
+}
+
+//YYYY-MM-DD of a unix timestamp in utc
+pub fn format_date(timestamp: i64) -> String {
+  let (year, month, day) = civil_from_days(timestamp.div_euclid(SECONDS_PER_DAY));
+  format!("{year:04}-{month:02}-{day:02}")
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+  let days = days + 719468;
+  let era = days.div_euclid(146097);
+  let day_of_era = days - era * 146097;
+  let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+  let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+  let month_index = (5 * day_of_year + 2) / 153;
+  let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+  let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+  let year = year_of_era + era * 400 + i64::from(month <= 2);
+  (year, month, day)
+}
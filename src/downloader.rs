@@ -1,63 +1,262 @@
-use headless_chrome::{Browser, LaunchOptions};
-use std::{sync::Arc, time::Duration};
-use tracing::{info, error};
+use clap::ValueEnum;
+use futures::future::join_all;
+use reqwest::{
+  cookie::Jar,
+  header::{HeaderMap, HeaderName, HeaderValue},
+};
+use serde::Deserialize;
+use std::{collections::HashMap, path::PathBuf, sync::Arc, time::Duration};
+use tokio::sync::{Mutex, Semaphore};
+use tracing::{error, info};
 
 use crate::{
+  archive::DownloadArchive,
+  browser::{BrowserManager, BrowserOptions},
+  config::PlatformConfig,
+  cookies::{self, Cookie},
+  dates,
   downloader_error::DownloaderError,
-  platforms::{tiktok::TiktokDownloader, twitter::TwitterDownloader},
+  filename::{self, DEFAULT_OUTPUT_TEMPLATE},
+  format::Format,
+  platforms::{tiktok::TiktokDownloader, tiktok_collection, twitter::TwitterDownloader},
 };
 
-#[derive(Clone)]
+const DEFAULT_INTERCEPT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum PreferredResolution {
   High,
   Medium,
   Low,
 }
 
+#[derive(Clone, Copy, PartialEq, Default, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GifFormat {
+  #[default]
+  Mp4,
+  Gif,
+  Webp,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SoundFormat {
+  M4a,
+  Mp3,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VideoCodec {
+  H264,
+  H265,
+}
+
 pub trait PlatformDownloader {
-  async fn download(
-    browser: Arc<Browser>, url: &str, preferred_resolution: Option<PreferredResolution>,
-  ) -> Result<String, DownloaderError>;
+  const NAME: &'static str;
+  //sites the cookies configured for the platform are sent to
+  const COOKIE_DOMAINS: &'static [&'static str];
+
+  async fn download(ctx: &DownloadContext, url: &str) -> Result<Vec<String>, DownloaderError>;
+  async fn list_formats(ctx: &DownloadContext, url: &str) -> Result<Vec<Format>, DownloaderError>;
   fn validate_url(url: &str) -> Result<(), DownloaderError>;
+  fn extract_id(url: &str) -> Option<String>;
+
+  //profiles and other pages listing many posts, each post is downloaded on its own
+  fn is_collection_url(_ctx: &DownloadContext, _url: &str) -> bool {
+    false
+  }
+
+  async fn list_collection(_ctx: &DownloadContext, _url: &str) -> Result<Vec<String>, DownloaderError> {
+    Err(DownloaderError::UnsupportedPlatformError)
+  }
+
+  //short links are expanded before anything else so naming and the archive see the canonical url
+  async fn resolve_url(_ctx: &DownloadContext, url: &str) -> Result<String, DownloaderError> {
+    Ok(url.to_string())
+  }
+}
+
+//dates are unix timestamps of midnight utc, both ends are inclusive
+#[derive(Clone, Default)]
+pub struct CollectionFilter {
+  pub max_count: Option<usize>,
+  pub date_after: Option<i64>,
+  pub date_before: Option<i64>,
+}
+
+impl CollectionFilter {
+  pub fn is_too_old(&self, timestamp: i64) -> bool {
+    self.date_after.is_some_and(|date_after| timestamp < date_after)
+  }
+
+  pub fn is_too_new(&self, timestamp: i64) -> bool {
+    self.date_before.is_some_and(|date_before| timestamp >= date_before + dates::SECONDS_PER_DAY)
+  }
+
+  pub fn has_date_range(&self) -> bool {
+    self.date_after.is_some() || self.date_before.is_some()
+  }
+
+  pub fn is_full(&self, count: usize) -> bool {
+    self.max_count.is_some_and(|max_count| count >= max_count)
+  }
+}
+
+//options every download reads, shared as is by the downloader and each download's context
+#[derive(Clone)]
+pub struct DownloadSettings {
+  pub output_template: String,
+  pub ffmpeg_path: PathBuf,
+  pub capture_response: bool,
+  pub include_images: bool,
+  pub gif_format: GifFormat,
+  pub preferred_codec: Option<VideoCodec>,
+  pub write_info_json: bool,
+  pub collection_filter: CollectionFilter,
+  pub thread: bool,
+  pub thread_quotes: bool,
+  pub slideshow_video: bool,
+  pub sound_only: Option<SoundFormat>,
+  pub log_media_requests: bool,
+}
+
+impl Default for DownloadSettings {
+  fn default() -> Self {
+    Self {
+      output_template: DEFAULT_OUTPUT_TEMPLATE.to_string(),
+      ffmpeg_path: PathBuf::from("ffmpeg"),
+      capture_response: false,
+      include_images: false,
+      gif_format: GifFormat::Mp4,
+      preferred_codec: None,
+      write_info_json: false,
+      collection_filter: CollectionFilter::default(),
+      thread: false,
+      thread_quotes: false,
+      slideshow_video: false,
+      sound_only: None,
+      log_media_requests: false,
+    }
+  }
+}
+
+pub struct DownloaderOptions {
+  pub archive: Option<DownloadArchive>,
+  pub concurrency: usize,
+  pub browser: BrowserOptions,
+  pub cookies: Vec<Cookie>,
+  pub platforms: HashMap<String, PlatformConfig>,
+  pub settings: DownloadSettings,
+}
+
+impl Default for DownloaderOptions {
+  fn default() -> Self {
+    Self {
+      archive: None,
+      concurrency: 4,
+      browser: BrowserOptions::default(),
+      cookies: vec![],
+      platforms: HashMap::new(),
+      settings: DownloadSettings::default(),
+    }
+  }
+}
+
+#[derive(Clone)]
+pub struct DownloadContext {
+  pub browser: Arc<BrowserManager>,
+  pub client: reqwest::Client,
+  pub cookie_jar: Arc<Jar>,
+  pub headers: HeaderMap,
+  pub timeout: Duration,
+  //the twitter api's guest token, fetched once and shared by every download
+  pub guest_token: Arc<Mutex<Option<String>>>,
+  pub settings: DownloadSettings,
+  pub preferred_resolution: Option<PreferredResolution>,
+}
+
+impl DownloadContext {
+  //configured headers take precedence over the platform defaults
+  pub fn get(&self, url: &str, default_headers: HeaderMap) -> reqwest::RequestBuilder {
+    self.client.get(url).headers(default_headers).headers(self.headers.clone())
+  }
+
+  pub async fn output_path(&self, fields: &HashMap<&str, String>) -> Result<String, DownloaderError> {
+    let mut path = filename::render_template(&self.settings.output_template, fields);
+    //posts with several media files get an index suffix and thread posts a position prefix unless the template already places them
+    let index = fields.get("index").filter(|_| !self.settings.output_template.contains("{index}"));
+    let thread_position = fields.get("thread_position").filter(|_| !self.settings.output_template.contains("{thread_position}"));
+    if index.is_some() || thread_position.is_some() {
+      let mut stem = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+      if let Some(thread_position) = thread_position {
+        stem = format!("{thread_position}_{stem}");
+      }
+      if let Some(index) = index {
+        stem = format!("{stem}_{index}");
+      }
+      let file_name = match path.extension() {
+        Some(ext) => format!("{stem}.{}", ext.to_string_lossy()),
+        None => stem,
+      };
+      path.set_file_name(file_name);
+    }
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+      tokio::fs::create_dir_all(parent).await.map_err(|_| DownloaderError::IOError)?;
+    }
+
+    Ok(path.to_string_lossy().to_string())
+  }
 }
 
 pub struct Downloader {
-  browser: Arc<Browser>,
+  browser: Arc<BrowserManager>,
+  client: reqwest::Client,
+  cookie_jar: Arc<Jar>,
+  archive: Option<DownloadArchive>,
+  platform_headers: HashMap<String, HeaderMap>,
+  platform_timeouts: HashMap<String, Duration>,
+  guest_token: Arc<Mutex<Option<String>>>,
+  settings: DownloadSettings,
+  job_slots: Semaphore,
 }
 
 impl Downloader {
-  pub fn new() -> Self {
-    let browser = Browser::new(LaunchOptions {
-      idle_browser_timeout: Duration::from_secs(1e7 as u64),
-      args: vec![
-        std::ffi::OsStr::new("--incognito"),
-        std::ffi::OsStr::new("--mute-audio")
-      ],
-      ..Default::default()
-    })
-    .unwrap();
-
-    let pid = browser.get_process_id().unwrap();
-    let _ = ctrlc::set_handler(move || {
-      use std::process::Command;
+  pub fn new(options: DownloaderOptions) -> Result<Self, DownloaderError> {
+    //cookies set by the pages, the browser and the config are added to the same jar, so the cookies.txt ones are always sent with them
+    let cookie_jar = Arc::new(cookies::to_jar(&options.cookies));
 
-      #[cfg(target_os = "windows")]
-      {
-        let _ = Command::new("taskkill").args(&["/PID", &pid.to_string(), "/T", "/F"]).output();
+    let mut platform_headers = HashMap::new();
+    let mut platform_timeouts = HashMap::new();
+    for (platform, config) in &options.platforms {
+      platform_headers.insert(platform.clone(), Self::build_headers(config)?);
+      if let Some(timeout_secs) = config.timeout_secs {
+        platform_timeouts.insert(platform.clone(), Duration::from_secs(timeout_secs));
       }
-      #[cfg(target_os = "linux")]
-      {
-        let _ = Command::new("kill").args(&["-9", &format!("-{}", pid)]).output();
+      if let Some(platform_cookies) = &config.cookies {
+        for domain in Self::cookie_domains(platform) {
+          cookies::add_domain_cookies(&cookie_jar, platform_cookies, domain);
+        }
       }
-      info!("Killed browser process (PID: {})", pid);
-      info!("Shutting down...");
-      std::process::exit(0);
-    });
+    }
+    let client = reqwest::Client::builder().cookie_provider(cookie_jar.clone()).build().map_err(|e| DownloaderError::OtherError(e.to_string()))?;
 
-    Self { browser: Arc::new(browser) }
+    Ok(Self {
+      browser: Arc::new(BrowserManager::new(BrowserOptions { cookies: options.cookies, ..options.browser })),
+      client,
+      cookie_jar,
+      archive: options.archive,
+      platform_headers,
+      platform_timeouts,
+      guest_token: Arc::new(Mutex::new(None)),
+      settings: options.settings,
+      job_slots: Semaphore::new(options.concurrency.max(1)),
+    })
   }
 
-  pub async fn download(&self, url: &str, preferred_resolution: Option<PreferredResolution>) -> Result<String, DownloaderError> {
+  pub async fn download(&self, url: &str, preferred_resolution: Option<PreferredResolution>) -> Result<Vec<String>, DownloaderError> {
     let url = url.trim_end();
     info!("Recieved download call: {url}");
 
@@ -66,13 +265,49 @@ impl Downloader {
       return Err(DownloaderError::InvalidInputError);
     }
 
-    let browser_clone = self.browser.clone();
+    let url = &match self.resolve_url(url).await {
+      Ok(resolved_url) => resolved_url,
+      Err(e) => {
+        error!("Could not resolve url: {url} ({e})");
+        return Err(e);
+      }
+    };
+
+    match url {
+      _ if TwitterDownloader::is_collection_url(&self.context::<TwitterDownloader>(None), url) => {
+        return self.download_collection::<TwitterDownloader>(url, preferred_resolution).await
+      }
+      _ if TiktokDownloader::is_collection_url(&self.context::<TiktokDownloader>(None), url) => {
+        return self.download_collection::<TiktokDownloader>(url, preferred_resolution).await
+      }
+      _ => {}
+    }
+
+    let archive_key = self.archive_key(url);
+    if let (Some(archive), Some(key)) = (&self.archive, &archive_key) {
+      if !archive.begin(key) {
+        info!("Skipping {url}: {key} is already in the download archive");
+        return Err(DownloaderError::AlreadyDownloadedError);
+      }
+    }
+
+    let _job_slot = self.job_slots.acquire().await;
     let result = match url {
-      _ if TwitterDownloader::validate_url(url).is_ok() => TwitterDownloader::download(browser_clone, url, preferred_resolution).await,
-      _ if TiktokDownloader::validate_url(url).is_ok() => TiktokDownloader::download(browser_clone, url, preferred_resolution).await,
+      _ if TwitterDownloader::validate_url(url).is_ok() => {
+        TwitterDownloader::download(&self.context::<TwitterDownloader>(preferred_resolution), url).await
+      }
+      _ if TiktokDownloader::validate_url(url).is_ok() => {
+        TiktokDownloader::download(&self.context::<TiktokDownloader>(preferred_resolution), url).await
+      }
       _ => Err(DownloaderError::UnsupportedPlatformError),
     };
 
+    if let (Some(archive), Some(key)) = (&self.archive, &archive_key) {
+      if let Err(e) = archive.finish(key, result.is_ok()) {
+        error!("Failed to record {key} in the download archive ({e})");
+      }
+    }
+
     match result {
       Ok(output) => {
         info!("Downloaded completed for url: {url}");
@@ -85,7 +320,121 @@ impl Downloader {
     }
   }
 
+  //the listing holds a job slot only while paginating so the posts themselves can use every slot
+  async fn download_collection<P: PlatformDownloader>(
+    &self,
+    url: &str,
+    preferred_resolution: Option<PreferredResolution>,
+  ) -> Result<Vec<String>, DownloaderError> {
+    let listed = {
+      let _job_slot = self.job_slots.acquire().await;
+      P::list_collection(&self.context::<P>(preferred_resolution.clone()), url).await
+    };
+    let post_urls = match listed {
+      Ok(post_urls) => post_urls,
+      Err(e) => {
+        error!("Listing failed for url: {url} ({e})");
+        return Err(e);
+      }
+    };
+    info!("Found {} posts in {url}", post_urls.len());
+
+    let downloads = post_urls.iter().map(|post_url| Box::pin(self.download(post_url, preferred_resolution.clone())));
+    let results = join_all(downloads).await;
+
+    let mut outputs = vec![];
+    let mut failed = 0;
+    for result in results {
+      match result {
+        Ok(output) => outputs.extend(output),
+        Err(DownloaderError::AlreadyDownloadedError) => {}
+        Err(_) => failed += 1,
+      }
+    }
+    if failed > 0 {
+      error!("{failed} of {} posts failed for url: {url}", post_urls.len());
+    }
+
+    Ok(outputs)
+  }
+
+  pub async fn formats(&self, url: &str) -> Result<Vec<Format>, DownloaderError> {
+    if !Self::is_url(url) {
+      error!("Invalid input: {url}");
+      return Err(DownloaderError::InvalidInputError);
+    }
+
+    let url = &self.resolve_url(url).await?;
+    match url {
+      _ if TwitterDownloader::validate_url(url).is_ok() => TwitterDownloader::list_formats(&self.context::<TwitterDownloader>(None), url).await,
+      _ if TiktokDownloader::validate_url(url).is_ok() => TiktokDownloader::list_formats(&self.context::<TiktokDownloader>(None), url).await,
+      _ => Err(DownloaderError::UnsupportedPlatformError),
+    }
+  }
+
+  async fn resolve_url(&self, url: &str) -> Result<String, DownloaderError> {
+    let resolved_url = match url {
+      _ if TwitterDownloader::validate_url(url).is_ok() => TwitterDownloader::resolve_url(&self.context::<TwitterDownloader>(None), url).await?,
+      _ if TiktokDownloader::validate_url(url).is_ok() => TiktokDownloader::resolve_url(&self.context::<TiktokDownloader>(None), url).await?,
+      _ => return Ok(url.to_string()),
+    };
+
+    if resolved_url != url {
+      info!("Resolved {url} to {resolved_url}");
+    }
+    Ok(resolved_url)
+  }
+
+  fn context<P: PlatformDownloader>(&self, preferred_resolution: Option<PreferredResolution>) -> DownloadContext {
+    DownloadContext {
+      browser: self.browser.clone(),
+      client: self.client.clone(),
+      cookie_jar: self.cookie_jar.clone(),
+      headers: self.platform_headers.get(P::NAME).cloned().unwrap_or_default(),
+      timeout: self.platform_timeouts.get(P::NAME).copied().unwrap_or(DEFAULT_INTERCEPT_TIMEOUT),
+      guest_token: self.guest_token.clone(),
+      settings: self.settings.clone(),
+      preferred_resolution,
+    }
+  }
+
+  fn build_headers(config: &PlatformConfig) -> Result<HeaderMap, DownloaderError> {
+    let invalid_header = |name: &str| DownloaderError::ConfigError(format!("invalid header {name}"));
+
+    let mut headers = HeaderMap::new();
+    for (name, value) in &config.headers {
+      let header_name = HeaderName::from_bytes(name.as_bytes()).map_err(|_| invalid_header(name))?;
+      let header_value = HeaderValue::from_str(value).map_err(|_| invalid_header(name))?;
+      headers.insert(header_name, header_value);
+    }
+
+    Ok(headers)
+  }
+
+  fn cookie_domains(platform: &str) -> &'static [&'static str] {
+    match platform {
+      TwitterDownloader::NAME => TwitterDownloader::COOKIE_DOMAINS,
+      TiktokDownloader::NAME => TiktokDownloader::COOKIE_DOMAINS,
+      _ => &[],
+    }
+  }
+
+  //a post's sound is recorded apart from its video, a sound page by the id of the sound itself
+  fn archive_key(&self, url: &str) -> Option<String> {
+    match url {
+      _ if TwitterDownloader::validate_url(url).is_ok() => TwitterDownloader::extract_id(url).map(|id| format!("{}:{id}", TwitterDownloader::NAME)),
+      _ if self.settings.sound_only.is_some() && tiktok_collection::is_music_url(url) => {
+        tiktok_collection::music_id(url).map(|id| format!("{}-music:{id}", TiktokDownloader::NAME))
+      }
+      _ if self.settings.sound_only.is_some() && TiktokDownloader::validate_url(url).is_ok() => {
+        TiktokDownloader::extract_id(url).map(|id| format!("{}-sound:{id}", TiktokDownloader::NAME))
+      }
+      _ if TiktokDownloader::validate_url(url).is_ok() => TiktokDownloader::extract_id(url).map(|id| format!("{}:{id}", TiktokDownloader::NAME)),
+      _ => None,
+    }
+  }
+
   fn is_url(url: &str) -> bool {
-    return !url.is_empty() && url.starts_with("https://");
+    !url.is_empty() && url.starts_with("https://")
   }
 }
@@ -1,12 +1,19 @@
 use std::fmt::Display;
 
+#[allow(clippy::enum_variant_names)]
 pub enum DownloaderError {
   InvalidInputError,
   UnsupportedPlatformError,
   FetchError,
+  ForbiddenError,
+  NoMediaError,
   NoMasterPlaylistError,
   IOError,
   FfmpegError,
+  AlreadyDownloadedError,
+  BrowserError(String),
+  ConfigError(String),
+  CookiesError(String),
   OtherError(String),
 }
 
@@ -23,9 +30,15 @@ impl Display for DownloaderError {
       InvalidInputError => write!(f, "Invalid input"),
       UnsupportedPlatformError => write!(f, "Platform not supported"),
       FetchError => write!(f, "Failed to fetch data from external source"),
+      ForbiddenError => write!(f, "Access to external source was forbidden"),
+      NoMediaError => write!(f, "No downloadable media found"),
       NoMasterPlaylistError => write!(f, "No master playlist found"),
       IOError => write!(f, "Failed to perform IO operation"),
       FfmpegError => write!(f, "Failed to execute ffmpeg command"),
+      AlreadyDownloadedError => write!(f, "Already recorded in the download archive"),
+      BrowserError(e) => write!(f, "Browser unavailable: {}", e),
+      ConfigError(e) => write!(f, "Invalid config: {}", e),
+      CookiesError(e) => write!(f, "Invalid cookies file: {}", e),
       OtherError(e) => write!(f, "Error: {}", e),
     }
   }
@@ -0,0 +1,153 @@
+use regex::{Captures, Regex};
+use std::{
+  collections::HashMap,
+  path::{Component, Path, PathBuf},
+};
+
+const MAX_FILENAME_BYTES: usize = 200;
+const FALLBACK_NAME: &str = "video";
+const RESERVED_WINDOWS_NAMES: [&str; 22] = [
+  "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6",
+  "LPT7", "LPT8", "LPT9",
+];
+
+pub fn sanitize(name: &str) -> String {
+  let mut sanitized: String = name
+    .chars()
+    .map(|c| match c {
+      '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+      c if c.is_control() => '_',
+      c => c,
+    })
+    .collect();
+
+  while sanitized.contains("..") {
+    sanitized = sanitized.replace("..", ".");
+  }
+  let mut sanitized = sanitized.trim_matches(|c: char| c == '.' || c.is_whitespace()).to_string();
+
+  let stem = sanitized.split('.').next().unwrap_or_default();
+  if RESERVED_WINDOWS_NAMES.iter().any(|reserved| reserved.eq_ignore_ascii_case(stem)) {
+    sanitized.insert(0, '_');
+  }
+
+  if sanitized.len() > MAX_FILENAME_BYTES {
+    sanitized = truncate_keeping_extension(&sanitized, MAX_FILENAME_BYTES);
+  }
+
+  if sanitized.is_empty() {
+    return FALLBACK_NAME.to_string();
+  }
+
+  sanitized
+}
+
+fn truncate_keeping_extension(name: &str, max_bytes: usize) -> String {
+  let (stem, extension) = match name.rfind('.') {
+    Some(index) if name.len() - index <= 16 => (&name[..index], &name[index..]),
+    _ => (name, ""),
+  };
+
+  let mut end = max_bytes.saturating_sub(extension.len()).min(stem.len());
+  while !stem.is_char_boundary(end) {
+    end -= 1;
+  }
+
+  format!("{}{}", &stem[..end], extension)
+}
+
+pub const DEFAULT_OUTPUT_TEMPLATE: &str = "{name}.{ext}";
+
+//fields are sanitized on their own so a value can never introduce a new path component
+pub fn render_template(template: &str, fields: &HashMap<&str, String>) -> PathBuf {
+  let placeholder_regex = Regex::new(r"\{(?P<key>\w+)\}").unwrap();
+  let rendered = placeholder_regex.replace_all(template, |captures: &Captures| match fields.get(&captures["key"]) {
+    Some(value) => sanitize(value),
+    None => "NA".to_string(),
+  });
+
+  let mut path = PathBuf::new();
+  for component in Path::new(rendered.as_ref()).components() {
+    match component {
+      Component::Prefix(_) | Component::RootDir => path.push(component.as_os_str()),
+      Component::Normal(name) => path.push(sanitize(&name.to_string_lossy())),
+      Component::CurDir | Component::ParentDir => {}
+    }
+  }
+
+  if path.file_name().is_none() {
+    path.push(FALLBACK_NAME);
+  }
+  path
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn render(template: &str, name: &str) -> PathBuf {
+    render_template(template, &HashMap::from([("name", name.to_string()), ("ext", "mp4".to_string())]))
+  }
+
+  #[test]
+  fn sanitize_removes_parent_directories() {
+    assert_eq!(sanitize("../etc/passwd"), "_etc_passwd");
+    assert_eq!(sanitize("..\\..\\windows"), "_._windows");
+    assert!(!sanitize("a/../../b").contains(".."));
+  }
+
+  #[test]
+  fn sanitize_escapes_reserved_names() {
+    assert_eq!(sanitize("CON.mp4"), "_CON.mp4");
+    assert_eq!(sanitize("nul"), "_nul");
+    assert_eq!(sanitize("console.mp4"), "console.mp4");
+  }
+
+  #[test]
+  fn sanitize_replaces_control_characters() {
+    assert_eq!(sanitize("a\nb\tc\u{7f}"), "a_b_c_");
+  }
+
+  #[test]
+  fn sanitize_falls_back_for_empty_names() {
+    assert_eq!(sanitize(""), FALLBACK_NAME);
+    assert_eq!(sanitize("..."), FALLBACK_NAME);
+    assert_eq!(sanitize(" . . "), FALLBACK_NAME);
+  }
+
+  #[test]
+  fn sanitize_truncates_on_char_boundaries() {
+    let sanitized = sanitize(&format!("{}.mp4", "é".repeat(150)));
+    assert!(sanitized.len() <= MAX_FILENAME_BYTES);
+    assert!(sanitized.ends_with(".mp4"));
+    assert_eq!(sanitized.trim_end_matches(".mp4"), "é".repeat((MAX_FILENAME_BYTES - 4) / 2));
+  }
+
+  #[test]
+  fn render_template_keeps_fields_in_one_component() {
+    let path = render(DEFAULT_OUTPUT_TEMPLATE, "../../etc/passwd");
+    assert_eq!(path.components().count(), 1);
+    assert!(path.components().all(|component| matches!(component, Component::Normal(_))));
+
+    let path = render("downloads/{name}.{ext}", "..\\..\\x");
+    assert_eq!(path, Path::new("downloads").join("_._x.mp4"));
+  }
+
+  #[test]
+  fn render_template_drops_parent_directories_of_the_template() {
+    assert_eq!(render("../{name}.{ext}", "video"), Path::new("video.mp4"));
+    assert_eq!(render("a/../../{name}.{ext}", "video"), Path::new("a").join("video.mp4"));
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn render_template_keeps_absolute_templates() {
+    assert_eq!(render("/tmp/{name}.{ext}", "../x"), Path::new("/tmp/_x.mp4"));
+  }
+
+  #[test]
+  fn render_template_marks_missing_fields() {
+    assert_eq!(render("{id}.{ext}", "video"), Path::new("NA.mp4"));
+    assert_eq!(render("{name}/", ""), Path::new(FALLBACK_NAME));
+  }
+}
@@ -0,0 +1,27 @@
+use serde::Serialize;
+use std::fmt::Display;
+
+use crate::downloader::PreferredResolution;
+
+#[derive(Clone, Serialize)]
+pub struct Format {
+  pub id: String,
+  pub resolution: Option<String>,
+  pub url: String,
+}
+
+impl Display for Format {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let resolution = self.resolution.as_deref().unwrap_or("unknown");
+    write!(f, "{:<16} {:<12} {}", self.id, resolution, self.url)
+  }
+}
+
+//formats have to be sorted from the highest to the lowest quality
+pub fn preferred_index(preferred_resolution: &Option<PreferredResolution>, format_count: usize) -> usize {
+  match preferred_resolution {
+    None | Some(PreferredResolution::High) => 0,
+    Some(PreferredResolution::Medium) => format_count / 2,
+    Some(PreferredResolution::Low) => format_count.saturating_sub(1),
+  }
+}
@@ -0,0 +1,40 @@
+use serde::Serialize;
+use std::path::Path;
+
+use crate::{downloader_error::DownloaderError, format::Format};
+
+//shared by every platform so archived sidecars can be read without knowing where they came from
+#[derive(Serialize, Clone, Default)]
+pub struct InfoJson {
+  pub platform: String,
+  pub id: Option<String>,
+  pub webpage_url: String,
+  pub text: Option<String>,
+  pub uploader: Option<String>,
+  pub uploader_name: Option<String>,
+  pub timestamp: Option<i64>,
+  pub like_count: Option<u64>,
+  pub repost_count: Option<u64>,
+  pub reply_count: Option<u64>,
+  pub quote_count: Option<u64>,
+  pub view_count: Option<u64>,
+  pub format: Option<Format>,
+  pub media_urls: Vec<String>,
+}
+
+impl InfoJson {
+  pub fn new(platform: &str, id: Option<String>, webpage_url: &str) -> Self {
+    InfoJson { platform: platform.to_string(), id, webpage_url: webpage_url.to_string(), ..Default::default() }
+  }
+
+  pub fn with_format(&self, format: Format) -> Self {
+    InfoJson { format: Some(format), ..self.clone() }
+  }
+}
+
+//written next to the downloaded file, e.g. video.mp4 -> video.info.json
+pub async fn write(output_name: &str, info: &InfoJson) -> Result<(), DownloaderError> {
+  let path = Path::new(output_name).with_extension("info.json");
+  let content = serde_json::to_string_pretty(info).map_err(|e| DownloaderError::OtherError(e.to_string()))?;
+  tokio::fs::write(path, content).await.map_err(|_| DownloaderError::IOError)
+}
@@ -1,23 +1,31 @@
-use downloader::{Downloader, PreferredResolution};
+use archive::DownloadArchive;
+use browser::BrowserOptions;
+use clap::{CommandFactory, Parser};
+use cli::{Cli, Command, DownloadArgs};
+use config::Config;
+use downloader::{CollectionFilter, DownloadSettings, Downloader, DownloaderOptions};
 use std::{
-  env::args,
   error::Error,
   io::{self, Write},
   sync::Arc,
 };
 use tracing_subscriber::fmt::format::FmtSpan;
 
+mod archive;
+mod batch;
+mod browser;
+mod cli;
+mod config;
+mod cookies;
+mod dates;
 mod downloader;
 mod downloader_error;
+mod filename;
+mod format;
+mod info_json;
 mod platforms;
 mod playlist;
 
-struct InputArgs {
-  url: String,
-  keep_alive: bool,
-  resolution: Option<PreferredResolution>,
-}
-
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
   tracing_subscriber::fmt()
@@ -32,72 +40,107 @@ async fn main() -> Result<(), Box<dyn Error>> {
     .with_thread_names(false)
     .init();
 
-  let args: Vec<String> = args().collect();
-  if !(args.contains(&String::from("-a")) || args.contains(&String::from("-i"))) {
-    const USAGE: &str = "Usage: vid-downloader [options]\n\
-    Options:\n\
-    -i --input: input url\n\
-    -a --keep-alive: keep handling incoming links (type exit to quit)\n\
-    ";
-    println!("{}", USAGE);
-    return Ok(());
+  let cli = Cli::parse();
+  let mut config = Config::load(cli.config.as_deref()).map_err(|e| e.to_string())?;
+  if cli.cookies.is_some() {
+    config.cookies_file = cli.cookies;
   }
-
-  let input = parse_input(args);
-  let downloader = Arc::new(Downloader::new());
-  if !input.keep_alive {
-    let downloader_clone = downloader.clone();
-    let resolution_clone = input.resolution.clone();
-    let _ = tokio::spawn(async move { downloader_clone.download(&input.url, resolution_clone).await }).await;
-  }
-
-  while input.keep_alive {
-    let mut new_url = String::new();
-    io::stdout().flush().unwrap();
-    if io::stdin().read_line(&mut new_url).is_err() {
-      eprintln!("Failed to read line");
-      continue;
+  match cli.command {
+    Command::Download { url, options } => {
+      let resolution = options.resolution.clone().or(config.resolution.clone());
+      let downloader = create_downloader(config, &options)?;
+      let _ = tokio::spawn(async move { downloader.download(url.trim(), resolution).await }).await;
     }
-    if new_url.trim().to_lowercase() == "exit" {
-      break;
+    Command::Formats { url, browser } => {
+      let downloader = create_downloader(config, &DownloadArgs { browser, ..Default::default() })?;
+      let formats = downloader.formats(url.trim()).await.map_err(|e| e.to_string())?;
+      for format in formats {
+        println!("{}", format);
+      }
     }
+    Command::Serve { options } => {
+      let resolution = options.resolution.clone().or(config.resolution.clone());
+      let downloader = create_downloader(config, &options)?;
+      loop {
+        let mut new_url = String::new();
+        io::stdout().flush().unwrap();
+        if io::stdin().read_line(&mut new_url).is_err() {
+          eprintln!("Failed to read line");
+          continue;
+        }
+        if new_url.trim().to_lowercase() == "exit" {
+          break;
+        }
 
-    let downloader_clone = downloader.clone();
-    let resolution_clone = input.resolution.clone();
-    tokio::spawn(async move {
-      let _ = downloader_clone.download(&new_url, resolution_clone).await;
-    });
+        let downloader_clone = downloader.clone();
+        let resolution_clone = resolution.clone();
+        tokio::spawn(async move {
+          let _ = downloader_clone.download(&new_url, resolution_clone).await;
+        });
+      }
+    }
+    Command::Batch { file, options } => {
+      let resolution = options.resolution.clone().or(config.resolution.clone());
+      let downloader = create_downloader(config, &options)?;
+      let urls = batch::read_urls(&file)?;
+      let report = batch::run(&downloader, urls, resolution).await;
+      print!("{}", report);
+    }
+    Command::Completions { shell } => {
+      clap_complete::generate(shell, &mut Cli::command(), "vid-downloader", &mut io::stdout());
+    }
+    Command::Man => {
+      clap_mangen::Man::new(Cli::command()).render(&mut io::stdout())?;
+    }
   }
 
   Ok(())
 }
 
-fn parse_input(args: Vec<String>) -> InputArgs {
-  let mut input = InputArgs { url: String::new(), keep_alive: false, resolution: None };
+//command line flags take precedence over config values
+fn create_downloader(config: Config, options: &DownloadArgs) -> Result<Arc<Downloader>, Box<dyn Error>> {
+  let archive = match options.download_archive.as_ref().or(config.download_archive.as_ref()) {
+    Some(path) => Some(DownloadArchive::open(path).map_err(|e| e.to_string())?),
+    None => None,
+  };
 
-  let mut i = 1;
-  while i < args.len() {
-    match args[i].as_str() {
-      "--input" | "-i" if i + 1 < args.len() => {
-        input.url = args[i + 1].clone().trim().to_string();
-        i += 1;
-      }
-      "--keep-alive" | "-a" => {
-        input.keep_alive = true;
-      }
-      "--high" | "-h" => {
-        input.resolution = Some(PreferredResolution::High);
-      }
-      "--medium" | "-m" => {
-        input.resolution = Some(PreferredResolution::Medium);
-      }
-      "--low" | "-l" => {
-        input.resolution = Some(PreferredResolution::Low);
-      }
-      _ => {}
-    }
-    i += 1;
-  }
+  let cookies = match &config.cookies_file {
+    Some(path) => cookies::load(path).map_err(|e| e.to_string())?,
+    None => vec![],
+  };
+
+  let defaults = DownloaderOptions::default();
+  let downloader = Downloader::new(DownloaderOptions {
+    archive,
+    concurrency: options.concurrency.or(config.concurrency).unwrap_or(defaults.concurrency),
+    browser: BrowserOptions {
+      ws_url: options.browser.browser_ws.clone().or(config.browser.ws_url),
+      path: options.browser.chrome_path.clone().or(config.browser.path),
+      headless: !options.browser.headful && config.browser.headless.unwrap_or(defaults.browser.headless),
+      args: [config.browser.args, options.browser.browser_arg.clone()].concat(),
+      user_data_dir: options.browser.user_data_dir.clone().or(config.browser.user_data_dir),
+      max_tabs: options.browser.max_tabs.or(config.browser.max_tabs).unwrap_or(defaults.browser.max_tabs),
+      ..Default::default()
+    },
+    cookies,
+    platforms: config.platforms,
+    settings: DownloadSettings {
+      output_template: options.output.clone().or(config.output_template).unwrap_or(defaults.settings.output_template),
+      ffmpeg_path: options.ffmpeg.clone().or(config.ffmpeg_path).unwrap_or(defaults.settings.ffmpeg_path),
+      capture_response: options.capture_response || config.capture_response.unwrap_or(defaults.settings.capture_response),
+      include_images: options.include_images || config.include_images.unwrap_or(defaults.settings.include_images),
+      gif_format: options.gif_format.or(config.gif_format).unwrap_or(defaults.settings.gif_format),
+      preferred_codec: options.codec.or(config.codec),
+      write_info_json: options.write_info_json || config.write_info_json.unwrap_or(defaults.settings.write_info_json),
+      collection_filter: CollectionFilter { max_count: options.max_count, date_after: options.date_after, date_before: options.date_before },
+      thread: options.thread || options.thread_quotes,
+      thread_quotes: options.thread_quotes,
+      slideshow_video: options.slideshow_video || config.slideshow_video.unwrap_or(defaults.settings.slideshow_video),
+      sound_only: options.sound_only.or(config.sound_only),
+      log_media_requests: options.log_media_requests || config.log_media_requests.unwrap_or(defaults.settings.log_media_requests),
+    },
+  })
+  .map_err(|e| e.to_string())?;
 
-  input
+  Ok(Arc::new(downloader))
 }
@@ -1,2 +1,8 @@
 pub mod tiktok;
-pub mod twitter;
\ No newline at end of file
+pub mod tiktok_collection;
+pub mod tiktok_hydration;
+pub mod tiktok_slideshow;
+pub mod tiktok_sound;
+pub mod twitter;
+pub mod twitter_api;
+pub mod twitter_spaces;
\ No newline at end of file
@@ -4,102 +4,338 @@ use headless_chrome::{
     transport::{SessionId, Transport},
   },
   protocol::cdp::{
-    Fetch::{events::RequestPausedEvent, RequestPattern, RequestStage},
-    Network::ResourceType,
-    Target::CreateTarget,
+    Fetch::{events::RequestPausedEvent, FailRequest, RequestPattern, RequestStage},
+    Network::{ErrorReason, ResourceType},
   },
-  Browser,
 };
-use std::sync::Arc;
-use std::time::Duration;
-use tokio::sync::Mutex;
+use reqwest::{
+  header::{HeaderMap, HeaderValue, REFERER, USER_AGENT},
+  StatusCode,
+};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
+use tracing::{info, warn};
 
 use crate::{
-  downloader::{PlatformDownloader, PreferredResolution},
+  browser::take_response_body,
+  cookies,
+  downloader::{DownloadContext, PlatformDownloader, PreferredResolution, VideoCodec},
   downloader_error::DownloaderError,
+  format::{self, Format},
+  info_json::{self, InfoJson},
+  platforms::{
+    tiktok_collection,
+    tiktok_hydration::{self, BitrateInfo, ItemStruct},
+    tiktok_slideshow, tiktok_sound,
+  },
 };
 
 pub struct TiktokDownloader {}
 
 impl PlatformDownloader for TiktokDownloader {
-  async fn download(browser: Arc<Browser>, url: &str, _preferred_resolution: Option<PreferredResolution>) -> Result<String, DownloaderError> {
-    let target = get_initial_tab_create_target();
-    let tab = browser.new_tab_with_options(target)?;
-    let intercepted_url = Arc::new(Mutex::new(String::new()));
-    let intercepted_cookie = Arc::new(Mutex::new(String::new()));
-    let interceptor = get_interceptor(intercepted_url.clone(), intercepted_cookie.clone());
-
-    tab.enable_fetch(Some(&get_request_patterns()), None)?;
-    tab.enable_request_interception(interceptor)?;
-    tab.navigate_to(url)?;
-
-    let mut found = false;
-    let mut timeout = 10.0 as f32;
-    while !found && timeout >= 0.0 {
-      found = !intercepted_url.lock().await.is_empty();
-      tokio::time::sleep(Duration::from_millis(100)).await;
-      timeout -= 0.1;
+  const NAME: &'static str = "tiktok";
+  const COOKIE_DOMAINS: &'static [&'static str] = &["tiktok.com"];
+
+  async fn download(ctx: &DownloadContext, url: &str) -> Result<Vec<String>, DownloaderError> {
+    let last_segment = url.split('?').next().unwrap_or_default().split('/').rfind(|s| !s.is_empty()).unwrap_or("video").trim_end_matches(".html");
+    let mut fields = HashMap::from([("platform", Self::NAME.to_string()), ("name", last_segment.to_string()), ("ext", "mp4".to_string())]);
+    if let Some(id) = Self::extract_id(url) {
+      fields.insert("id", id);
     }
-    let _ = tab.close(false);
 
-    if !found {
-      return Err(DownloaderError::FetchError);
+    if let Some(sound_format) = ctx.settings.sound_only {
+      let music = if tiktok_collection::is_music_url(url) {
+        tiktok_hydration::resolve_music(ctx, url).await?
+      } else {
+        tiktok_hydration::resolve_item(ctx, url).await?.music.ok_or(DownloaderError::NoMediaError)?
+      };
+      return tiktok_sound::download(ctx, url, &music, sound_format, fields).await;
     }
 
-    let client = reqwest::Client::new();
-
-    let url_mutex_guard = intercepted_url.lock().await.to_owned();
-    let cookie_mutex_guard = intercepted_cookie.lock().await.to_owned();
-    let response = client
-      .get(url_mutex_guard)
-      .header(
-        "User-Agent",
-        r"Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:133.0) Gecko/20100101 Firefox/133.0",
-      )
-      .header("Referer", r"https://www.tiktok.com/")
-      .header("Cookie", cookie_mutex_guard)
-      .send()
-      .await
-      .map_err(|_| DownloaderError::FetchError)?;
-
-    let bytes = response.bytes().await.map_err(|_| DownloaderError::FetchError)?;
-
-    let mut output_name = url.split('/').filter(|s| !s.is_empty()).last().unwrap_or("video").to_string();
-    if output_name.contains('?') {
-      output_name = output_name.split('?').next().unwrap().to_string();
+    //the embedded page data lists every variant along with the post's metadata, intercepting the player is the fallback
+    let hydrated = match tiktok_hydration::resolve_item(ctx, url).await {
+      Ok(hydrated) => Some(hydrated),
+      Err(e) if is_photo_url(url) => return Err(e),
+      Err(e) => {
+        warn!("Could not read the page data, intercepting the video instead: {url} ({e})");
+        None
+      }
+    };
+    if let Some(item) = hydrated.as_ref().filter(|item| !item.images().is_empty()) {
+      return tiktok_slideshow::download(ctx, url, item, fields).await;
     }
-    output_name.push_str(".mp4");
+
+    let (bytes, format, item) = match hydrated {
+      Some(item) if !ctx.settings.capture_response => match fetch_hydrated_video(ctx, &item).await {
+        Ok((bytes, format)) => (bytes, Some(format), Some(item)),
+        Err(e) => {
+          warn!("Could not download the video from the page data, intercepting it instead: {url} ({e})");
+          let (bytes, format) = intercept_video(ctx, url).await?;
+          (bytes, format, Some(item))
+        }
+      },
+      hydrated => {
+        let (bytes, format) = intercept_video(ctx, url).await?;
+        (bytes, format, hydrated)
+      }
+    };
+
+    let output_name = ctx.output_path(&fields).await?;
 
     tokio::fs::write(&output_name, bytes).await.map_err(|_| DownloaderError::IOError)?;
 
-    Ok(output_name)
+    if ctx.settings.write_info_json {
+      let mut info = item.map(|item| item.info(url)).unwrap_or_else(|| InfoJson::new(Self::NAME, Self::extract_id(url), url));
+      if let Some(format) = format {
+        info.media_urls.push(format.url.clone());
+        info.format = Some(format);
+      }
+      info_json::write(&output_name, &info).await?;
+    }
+
+    Ok(vec![output_name])
+  }
+
+  async fn list_formats(ctx: &DownloadContext, url: &str) -> Result<Vec<Format>, DownloaderError> {
+    match tiktok_hydration::resolve_item(ctx, url).await {
+      Ok(item) if !item.bitrate_info().is_empty() => return Ok(variant_formats(item.bitrate_info())),
+      Ok(_) => {}
+      Err(e) => warn!("Could not read the page data, intercepting the video instead: {url} ({e})"),
+    }
+
+    let intercepted = intercept_video_request(ctx, url).await?;
+
+    let item = intercepted.hydration.as_deref().and_then(|hydration| tiktok_hydration::parse_item(hydration).ok());
+    let formats = item.as_ref().map(|item| variant_formats(item.bitrate_info())).unwrap_or_default();
+    if formats.is_empty() {
+      return Ok(vec![Format { id: "mp4".to_string(), resolution: None, url: intercepted.video_url }]);
+    }
+
+    Ok(formats)
   }
 
   fn validate_url(url: &str) -> Result<(), DownloaderError> {
-    let tiktok_regex = regex::Regex::new(r"https:\/\/(www\.)?tiktok.com\/@.+\/video\/\d+(\?.*)?").unwrap();
+    let tiktok_regex = regex::Regex::new(r"https:\/\/(www\.)?tiktok.com\/@.+\/(video|photo)\/\d+(\?.*)?").unwrap();
     let tiktok_short_regex = regex::Regex::new(r"https:\/\/(www\.)?\w+\.tiktok\.com\/[^@]\w+").unwrap();
+    let tiktok_mobile_regex = regex::Regex::new(r"https:\/\/m\.tiktok\.com\/v\/\d+").unwrap();
 
-    if !tiktok_regex.is_match(url) && !tiktok_short_regex.is_match(url) {
+    if !tiktok_regex.is_match(url) && !tiktok_short_regex.is_match(url) && !tiktok_mobile_regex.is_match(url) && !tiktok_collection::is_music_url(url)
+    {
       return Err(DownloaderError::UnsupportedPlatformError);
     }
 
     Ok(())
   }
+
+  fn extract_id(url: &str) -> Option<String> {
+    //older links and some redirects use the mobile shape m.tiktok.com/v/<id>.html
+    let id_regex = regex::Regex::new(r"\/(video|photo|v)\/(?P<id>\d+)").unwrap();
+    id_regex.captures(url).and_then(|captures| captures.name("id")).map(|id| id.as_str().to_string())
+  }
+
+  //with --sound-only a sound page stands for its own sound rather than the posts using it
+  fn is_collection_url(ctx: &DownloadContext, url: &str) -> bool {
+    tiktok_collection::is_collection_url(url) && !(ctx.settings.sound_only.is_some() && tiktok_collection::is_music_url(url))
+  }
+
+  async fn list_collection(ctx: &DownloadContext, url: &str) -> Result<Vec<String>, DownloaderError> {
+    tiktok_collection::list(ctx, url).await
+  }
+
+  //short links redirect to the page of the post, which is the only place the video id appears
+  //when the redirect can't be followed the id is read from the data of the page the link opens, so naming and the archive still use it
+  async fn resolve_url(ctx: &DownloadContext, url: &str) -> Result<String, DownloaderError> {
+    if Self::extract_id(url).is_some() || tiktok_collection::is_collection_url(url) {
+      return Ok(url.to_string());
+    }
+
+    match ctx.get(url, media_headers()).send().await {
+      Ok(response) => {
+        let resolved_url = response.url().as_str();
+        let canonical_regex = regex::Regex::new(r"^https:\/\/(www\.)?tiktok\.com\/@[^\/?]+\/(video|photo)\/\d+").unwrap();
+        if let Some(canonical_url) = canonical_regex.find(resolved_url) {
+          return Ok(canonical_url.as_str().replacen("https://tiktok.com", "https://www.tiktok.com", 1));
+        }
+        if let Some(id) = Self::extract_id(resolved_url) {
+          return Ok(format!("https://m.tiktok.com/v/{id}.html"));
+        }
+        warn!("The short link did not redirect to a post, reading the post from its page instead: {url} (landed on {resolved_url})");
+      }
+      Err(e) => warn!("Could not follow the short link, reading the post from its page instead: {url} ({e})"),
+    }
+
+    tiktok_hydration::resolve_item(ctx, url).await?.canonical_url().ok_or(DownloaderError::FetchError)
+  }
+}
+
+struct InterceptedVideo {
+  video_url: String,
+  cookie: String,
+  hydration: Option<String>,
+}
+
+fn is_photo_url(url: &str) -> bool {
+  url.contains("/photo/")
+}
+
+//image and sound urls look like .../photomode-image.jpeg?x-expires=...
+pub fn url_extension(url: &str, default: &str) -> String {
+  let path = url.split('?').next().unwrap_or_default();
+  let file_name = path.split('/').next_back().unwrap_or_default();
+  match file_name.rsplit_once('.') {
+    Some((_, ext)) if !ext.is_empty() && ext.len() <= 4 && ext.chars().all(|c| c.is_ascii_alphanumeric()) => ext.to_lowercase(),
+    _ => default.to_string(),
+  }
+}
+
+pub fn media_headers() -> HeaderMap {
+  let mut headers = HeaderMap::new();
+  headers.insert(USER_AGENT, HeaderValue::from_static(r"Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:133.0) Gecko/20100101 Firefox/133.0"));
+  headers.insert(REFERER, HeaderValue::from_static(r"https://www.tiktok.com/"));
+
+  headers
+}
+
+async fn fetch_hydrated_video(ctx: &DownloadContext, item: &ItemStruct) -> Result<(Vec<u8>, Format), DownloaderError> {
+  let format = select_variant(ctx.settings.preferred_codec, &ctx.preferred_resolution, item.bitrate_info())
+    .and_then(variant_format)
+    .ok_or(DownloaderError::NoMediaError)?;
+  let bytes = fetch_media(ctx, &format.url).await?;
+  Ok((bytes, format))
+}
+
+//the media url is only known when the video was requested again outside the browser
+async fn intercept_video(ctx: &DownloadContext, url: &str) -> Result<(Vec<u8>, Option<Format>), DownloaderError> {
+  if ctx.settings.capture_response {
+    return Ok((capture_video_response(ctx, url).await?, None));
+  }
+
+  match fetch_video(ctx, url).await {
+    Err(DownloaderError::ForbiddenError) => {
+      warn!("Refetching the video was forbidden, capturing it from the browser instead: {url}");
+      Ok((capture_video_response(ctx, url).await?, None))
+    }
+    result => result.map(|(bytes, format)| (bytes, Some(format))),
+  }
+}
+
+async fn fetch_video(ctx: &DownloadContext, url: &str) -> Result<(Vec<u8>, Format), DownloaderError> {
+  let InterceptedVideo { video_url, cookie, hydration } = intercept_video_request(ctx, url).await?;
+
+  //the page only plays one variant, the hydration data lists every bitrate and codec
+  let item = hydration.as_deref().and_then(|hydration| tiktok_hydration::parse_item(hydration).ok());
+  let format = item
+    .and_then(|item| select_variant(ctx.settings.preferred_codec, &ctx.preferred_resolution, item.bitrate_info()).and_then(variant_format))
+    .unwrap_or(Format { id: "mp4".to_string(), resolution: None, url: video_url });
+
+  //the browser's cookies join the cookies.txt ones in the jar instead of replacing them in a header
+  cookies::add_header_cookies(&ctx.cookie_jar, &cookie, &format.url);
+  let bytes = fetch_media(ctx, &format.url).await?;
+  Ok((bytes, format))
+}
+
+pub async fn fetch_media(ctx: &DownloadContext, url: &str) -> Result<Vec<u8>, DownloaderError> {
+  let response = ctx.get(url, media_headers()).send().await.map_err(|_| DownloaderError::FetchError)?;
+  match response.status() {
+    StatusCode::FORBIDDEN => return Err(DownloaderError::ForbiddenError),
+    status if !status.is_success() => return Err(DownloaderError::FetchError),
+    _ => {}
+  }
+
+  let bytes = response.bytes().await.map_err(|_| DownloaderError::FetchError)?;
+  Ok(bytes.to_vec())
+}
+
+//streams the video out of the browser for signed urls that only work inside the page session
+async fn capture_video_response(ctx: &DownloadContext, url: &str) -> Result<Vec<u8>, DownloaderError> {
+  let tab = ctx.browser.open_tab().await?;
+  let (sender, receiver) = oneshot::channel();
+  let candidates = MediaCandidates::new(ctx.settings.log_media_requests);
+  let interceptor = get_response_interceptor(sender, candidates.clone());
+
+  tab.enable_fetch(Some(&get_request_patterns()), None)?;
+  tab.enable_request_interception(interceptor)?;
+  tab.navigate_to(url)?;
+
+  let captured = tokio::time::timeout(ctx.timeout, receiver).await;
+  drop(tab);
+  let Ok(Ok(body)) = captured else {
+    candidates.log(url);
+    return Err(DownloaderError::FetchError);
+  };
+
+  body
+}
+
+fn sorted_variants(variants: &[BitrateInfo]) -> Vec<&BitrateInfo> {
+  let mut variants: Vec<&BitrateInfo> = variants.iter().filter(|variant| variant.url().is_some()).collect();
+  variants.sort_by_key(|variant| std::cmp::Reverse((variant.pixels(), variant.bitrate.unwrap_or(0))));
+  variants
+}
+
+fn variant_formats(variants: &[BitrateInfo]) -> Vec<Format> {
+  sorted_variants(variants).into_iter().filter_map(variant_format).collect()
+}
+
+fn variant_format(variant: &BitrateInfo) -> Option<Format> {
+  let id = variant
+    .gear_name
+    .clone()
+    .unwrap_or_else(|| format!("{}-{}", variant.codec_type.as_deref().unwrap_or("mp4"), variant.bitrate.unwrap_or(0) / 1000));
+  Some(Format { id, resolution: variant.resolution(), url: variant.url()?.to_string() })
+}
+
+//the codec preference only narrows the choice when the post is available in that codec
+fn select_variant<'a>(
+  preferred_codec: Option<VideoCodec>,
+  preferred_resolution: &Option<PreferredResolution>,
+  variants: &'a [BitrateInfo],
+) -> Option<&'a BitrateInfo> {
+  let mut variants = sorted_variants(variants);
+  if let Some(codec) = preferred_codec {
+    if variants.iter().any(|variant| variant.codec() == Some(codec)) {
+      variants.retain(|variant| variant.codec() == Some(codec));
+    }
+  }
+
+  variants.get(format::preferred_index(preferred_resolution, variants.len())).copied()
 }
 
-fn get_interceptor(url: Arc<Mutex<String>>, cookie: Arc<Mutex<String>>) -> Arc<dyn RequestInterceptor + Send + Sync> {
+async fn intercept_video_request(ctx: &DownloadContext, url: &str) -> Result<InterceptedVideo, DownloaderError> {
+  let tab = ctx.browser.open_tab().await?;
+  let (sender, receiver) = oneshot::channel();
+  let candidates = MediaCandidates::new(ctx.settings.log_media_requests);
+  let interceptor = get_interceptor(sender, candidates.clone());
+
+  tab.enable_fetch(Some(&get_request_patterns()), None)?;
+  tab.enable_request_interception(interceptor)?;
+  tab.navigate_to(url)?;
+
+  let intercepted = tokio::time::timeout(ctx.timeout, receiver).await;
+  let Ok(Ok((video_url, cookie))) = intercepted else {
+    candidates.log(url);
+    return Err(DownloaderError::FetchError);
+  };
+
+  let hydration = tiktok_hydration::read_hydration(&tab);
+  drop(tab);
+
+  Ok(InterceptedVideo { video_url, cookie, hydration })
+}
+
+fn get_interceptor(sender: oneshot::Sender<(String, String)>, candidates: MediaCandidates) -> Arc<dyn RequestInterceptor + Send + Sync> {
+  let sender = Mutex::new(Some(sender));
   Arc::new(move |_transport: Arc<Transport>, _session_id: SessionId, event: RequestPausedEvent| {
-    let request = event.params.request.clone();
+    candidates.record(&event);
 
-    let mut url_mutex_guard = url.blocking_lock();
-    let mut cookie_mutex_guard = cookie.blocking_lock();
-    if request.url.contains("mime_type=video_mp4") && url_mutex_guard.is_empty() {
-      *url_mutex_guard = request.url;
+    if is_video_response(&event) {
+      if let Some(sender) = sender.lock().unwrap().take() {
+        let request = event.params.request;
+        let cookie =
+          request.headers.0.as_ref().and_then(|headers| headers.get("Cookie")).and_then(|cookie| cookie.as_str()).unwrap_or_default().to_string();
 
-      if let Some(header) = request.headers.0 {
-        if let Some(cookie_header) = header.get("Cookie") {
-          *cookie_mutex_guard = cookie_header.clone().take().to_string();
-        }
+        let _ = sender.send((request.url, cookie));
       }
     }
 
@@ -107,34 +343,136 @@ fn get_interceptor(url: Arc<Mutex<String>>, cookie: Arc<Mutex<String>>) -> Arc<d
   })
 }
 
+fn get_response_interceptor(
+  sender: oneshot::Sender<Result<Vec<u8>, DownloaderError>>,
+  candidates: MediaCandidates,
+) -> Arc<dyn RequestInterceptor + Send + Sync> {
+  let sender = Mutex::new(Some(sender));
+  Arc::new(move |transport: Arc<Transport>, session_id: SessionId, event: RequestPausedEvent| {
+    candidates.record(&event);
+
+    if is_video_response(&event) {
+      if let Some(sender) = sender.lock().unwrap().take() {
+        let request_id = event.params.request_id;
+        let _ = sender.send(take_response_body(&transport, session_id, request_id.clone()));
+        return RequestPausedDecision::Fail(FailRequest { request_id, error_reason: ErrorReason::Aborted });
+      }
+    }
+
+    RequestPausedDecision::Continue(None)
+  })
+}
+
+//the video is served from whichever cdn edge is closest, so it is recognised by what it serves rather than by its host
+fn is_video_response(event: &RequestPausedEvent) -> bool {
+  let params = &event.params;
+  let is_success = params.response_status_code.is_some_and(|status| (200..300).contains(&status));
+  let is_video_type = response_content_type(event).is_some_and(|content_type| content_type.starts_with("video/"));
+  let is_video_url = params.request.url.contains("mime_type=video_mp4") || params.request.url.contains("/video/tos/");
+
+  is_success && (is_video_type || is_video_url)
+}
+
+fn response_content_type(event: &RequestPausedEvent) -> Option<&str> {
+  let headers = event.params.response_headers.as_ref()?;
+  headers.iter().find(|header| header.name.eq_ignore_ascii_case("content-type")).map(|header| header.value.as_str())
+}
+
+//every media and xhr response is paused at the response stage, where its content type is known
 fn get_request_patterns() -> Vec<RequestPattern> {
-  vec![
-    RequestPattern {
-      url_pattern: Some("https://v16-webapp-prime.tiktok.com/video/*".to_string()),
-      resource_Type: Some(ResourceType::Xhr),
-      request_stage: Some(RequestStage::Request),
-    },
-    RequestPattern {
-      url_pattern: Some("https://v16-webapp-prime.tiktok.com/video/*".to_string()),
-      resource_Type: Some(ResourceType::Media),
-      request_stage: Some(RequestStage::Request),
-    },
-  ]
-}
-
-fn get_initial_tab_create_target() -> CreateTarget {
-  CreateTarget {
-    url: "about::blank".to_string(),
-    width: None,
-    height: None,
-    browser_context_id: None,
-    enable_begin_frame_control: None,
-    new_window: Some(true),
-    background: Some(true),
-    left: None,
-    top: None,
-    window_state: None,
-    for_tab: None,
-    hidden: None,
+  [ResourceType::Media, ResourceType::Xhr]
+    .into_iter()
+    .map(|resource_type| RequestPattern {
+      url_pattern: Some("*".to_string()),
+      resource_Type: Some(resource_type),
+      request_stage: Some(RequestStage::Response),
+    })
+    .collect()
+}
+
+//responses seen while looking for the video, only kept to be logged with --log-media-requests when none matched
+#[derive(Clone)]
+struct MediaCandidates(Option<Arc<Mutex<Vec<String>>>>);
+
+impl MediaCandidates {
+  fn new(enabled: bool) -> Self {
+    MediaCandidates(enabled.then(Default::default))
+  }
+
+  fn record(&self, event: &RequestPausedEvent) {
+    if let Some(candidates) = &self.0 {
+      let params = &event.params;
+      let status = params.response_status_code.map(|status| status.to_string()).unwrap_or_else(|| "-".to_string());
+      let content_type = response_content_type(event).unwrap_or("-");
+      candidates.lock().unwrap().push(format!("{:?} {status} {content_type} {}", params.resource_Type, params.request.url));
+    }
+  }
+
+  fn log(&self, url: &str) {
+    if let Some(candidates) = &self.0 {
+      let candidates = candidates.lock().unwrap();
+      warn!("No video response matched for {url}, {} candidate media requests were made", candidates.len());
+      for candidate in candidates.iter() {
+        info!("Candidate media request: {candidate}");
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const BITRATE_INFO: &str = r#"[
+    {"Bitrate": 1200000, "CodecType": "h264", "GearName": "h264_720", "PlayAddr": {"UrlList": ["https://v16.tiktok.com/h264_720"], "Width": 720, "Height": 1280}},
+    {"Bitrate": 2400000, "CodecType": "h264", "GearName": "h264_1080", "PlayAddr": {"UrlList": ["https://v16.tiktok.com/h264_1080"], "Width": 1080, "Height": 1920}},
+    {"Bitrate": 1500000, "CodecType": "bytevc1", "GearName": "h265_1080", "PlayAddr": {"UrlList": ["https://v16.tiktok.com/h265_1080"], "Width": 1080, "Height": 1920}},
+    {"Bitrate": 800000, "CodecType": "bytevc1", "GearName": "h265_720", "PlayAddr": {"UrlList": ["https://v16.tiktok.com/h265_720"], "Width": 720, "Height": 1280}},
+    {"Bitrate": 600000, "CodecType": "h264", "GearName": "h264_540", "PlayAddr": {"UrlList": ["https://v16.tiktok.com/h264_540"], "Width": 540, "Height": 960}},
+    {"Bitrate": 500000, "CodecType": "h264", "GearName": "no_url", "PlayAddr": {"UrlList": [], "Width": 1080, "Height": 1920}}
+  ]"#;
+
+  fn variants() -> Vec<BitrateInfo> {
+    serde_json::from_str(BITRATE_INFO).expect("valid bitrate info")
+  }
+
+  fn selected(codec: Option<VideoCodec>, resolution: Option<PreferredResolution>, variants: &[BitrateInfo]) -> Option<&str> {
+    select_variant(codec, &resolution, variants).and_then(|variant| variant.gear_name.as_deref())
+  }
+
+  #[test]
+  fn codec_recognizes_tiktok_codec_names() {
+    let codecs: Vec<Option<VideoCodec>> = variants().iter().map(BitrateInfo::codec).collect();
+    assert!(matches!(codecs[..], [Some(VideoCodec::H264), Some(VideoCodec::H264), Some(VideoCodec::H265), Some(VideoCodec::H265), ..]));
+  }
+
+  #[test]
+  fn sorted_variants_orders_by_resolution_then_bitrate_and_drops_variants_without_url() {
+    let variants = variants();
+    let names: Vec<&str> = sorted_variants(&variants).iter().filter_map(|variant| variant.gear_name.as_deref()).collect();
+    assert_eq!(names, ["h264_1080", "h265_1080", "h264_720", "h265_720", "h264_540"]);
+  }
+
+  #[test]
+  fn select_variant_prefers_the_requested_codec() {
+    let variants = variants();
+    assert_eq!(selected(Some(VideoCodec::H265), None, &variants), Some("h265_1080"));
+    assert_eq!(selected(Some(VideoCodec::H265), Some(PreferredResolution::Low), &variants), Some("h265_720"));
+    assert_eq!(selected(Some(VideoCodec::H264), Some(PreferredResolution::Low), &variants), Some("h264_540"));
+  }
+
+  #[test]
+  fn select_variant_falls_back_to_every_codec_when_the_preferred_one_is_missing() {
+    let variants: Vec<BitrateInfo> = variants().into_iter().filter(|variant| variant.codec() == Some(VideoCodec::H264)).collect();
+    assert_eq!(selected(Some(VideoCodec::H265), None, &variants), Some("h264_1080"));
+    assert_eq!(selected(Some(VideoCodec::H265), Some(PreferredResolution::Low), &variants), Some("h264_540"));
+  }
+
+  #[test]
+  fn select_variant_picks_by_resolution() {
+    let variants = variants();
+    assert_eq!(selected(None, Some(PreferredResolution::High), &variants), Some("h264_1080"));
+    assert_eq!(selected(None, Some(PreferredResolution::Medium), &variants), Some("h264_720"));
+    assert_eq!(selected(None, Some(PreferredResolution::Low), &variants), Some("h264_540"));
   }
 }
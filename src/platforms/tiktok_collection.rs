@@ -0,0 +1,139 @@
+use base64::{prelude::BASE64_STANDARD, Engine};
+use headless_chrome::{browser::tab::ResponseHandler, protocol::cdp::Network::events::ResponseReceivedEventParams};
+use serde::Deserialize;
+use std::collections::HashSet;
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tracing::warn;
+
+use crate::{
+  downloader::DownloadContext,
+  downloader_error::DownloaderError,
+  platforms::tiktok_hydration::{lenient_number, Author, ImagePost},
+};
+
+const ITEM_LIST_HANDLER: &str = "item_list";
+const ITEM_LIST_PATHS: [&str; 3] = ["/api/post/item_list/", "/api/challenge/item_list/", "/api/music/item_list/"];
+const SCROLL_SCRIPT: &str = "window.scrollTo(0, document.body.scrollHeight)";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ItemListPage {
+  #[serde(default)]
+  item_list: Vec<ListedItem>,
+  #[serde(default)]
+  has_more: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ListedItem {
+  id: String,
+  #[serde(default, deserialize_with = "lenient_number")]
+  create_time: Option<i64>,
+  author: Option<Author>,
+  image_post: Option<ImagePost>,
+  #[serde(default)]
+  is_pinned_item: bool,
+}
+
+impl ListedItem {
+  fn url(&self) -> Option<String> {
+    let author = self.author.as_ref()?.unique_id.as_deref()?;
+    let kind = if self.image_post.is_some() { "photo" } else { "video" };
+    Some(format!("https://www.tiktok.com/@{author}/{kind}/{}", self.id))
+  }
+}
+
+//profiles, hashtags and sounds, e.g. tiktok.com/@user, tiktok.com/tag/cats, tiktok.com/music/song-123
+pub fn is_collection_url(url: &str) -> bool {
+  collection_kind(url).is_some()
+}
+
+pub fn is_music_url(url: &str) -> bool {
+  collection_kind(url).as_deref() == Some("music/")
+}
+
+//sound pages end with the id of their sound, e.g. tiktok.com/music/<name>-<id>
+pub fn music_id(url: &str) -> Option<String> {
+  let music_regex = regex::Regex::new(r"^https:\/\/(www\.)?tiktok\.com\/music\/[^\/?#]*?-?(?P<id>\d+)\/?(\?.*)?$").unwrap();
+  music_regex.captures(url).and_then(|captures| captures.name("id")).map(|id| id.as_str().to_string())
+}
+
+fn collection_kind(url: &str) -> Option<String> {
+  let collection_regex = regex::Regex::new(r"^https:\/\/(www\.)?tiktok\.com\/(?P<kind>@|tag\/|music\/)[^\/?#]+\/?(\?.*)?$").unwrap();
+  collection_regex.captures(url).and_then(|captures| captures.name("kind")).map(|kind| kind.as_str().to_string())
+}
+
+//the page requests the item list api by itself while it is scrolled, so every response is read as it finishes loading
+pub async fn list(ctx: &DownloadContext, url: &str) -> Result<Vec<String>, DownloaderError> {
+  //only profiles are sorted newest first, apart from their pinned posts
+  let is_sorted = collection_kind(url).as_deref() == Some("@");
+
+  let tab = ctx.browser.open_tab().await?;
+  let (sender, mut receiver) = mpsc::unbounded_channel();
+  tab.register_response_handling(ITEM_LIST_HANDLER, get_item_list_handler(sender))?;
+  tab.navigate_to(url)?;
+
+  let filter = &ctx.settings.collection_filter;
+  let mut post_urls = vec![];
+  let mut listed_ids = HashSet::new();
+  let mut page_count = 0;
+  loop {
+    let page = match tokio::time::timeout(ctx.timeout, receiver.recv()).await {
+      Ok(Some(page)) => page,
+      _ if page_count == 0 => return Err(DownloaderError::FetchError),
+      _ => {
+        warn!("No more posts were loaded for {url}, stopping after {page_count} pages");
+        break;
+      }
+    };
+    page_count += 1;
+
+    for item in &page.item_list {
+      if !listed_ids.insert(item.id.clone()) {
+        continue;
+      }
+
+      //a post without a readable date can't be placed in the range, it is skipped instead of ending the listing
+      match item.create_time {
+        Some(timestamp) if is_sorted && !item.is_pinned_item && filter.is_too_old(timestamp) => return Ok(post_urls),
+        Some(timestamp) if filter.is_too_old(timestamp) || filter.is_too_new(timestamp) => continue,
+        None if filter.has_date_range() => continue,
+        _ => {}
+      }
+
+      post_urls.extend(item.url());
+      if filter.is_full(post_urls.len()) {
+        return Ok(post_urls);
+      }
+    }
+
+    if !page.has_more {
+      break;
+    }
+    tab.evaluate(SCROLL_SCRIPT, false)?;
+  }
+
+  Ok(post_urls)
+}
+
+fn get_item_list_handler(sender: UnboundedSender<ItemListPage>) -> ResponseHandler {
+  Box::new(move |params: ResponseReceivedEventParams, fetch_body| {
+    if !ITEM_LIST_PATHS.iter().any(|path| params.response.url.contains(path)) {
+      return;
+    }
+
+    let Ok(body) = fetch_body() else {
+      return;
+    };
+    let body = if body.base_64_encoded {
+      BASE64_STANDARD.decode(body.body).ok().and_then(|body| String::from_utf8(body).ok()).unwrap_or_default()
+    } else {
+      body.body
+    };
+
+    if let Ok(page) = serde_json::from_str::<ItemListPage>(&body) {
+      let _ = sender.send(page);
+    }
+  })
+}
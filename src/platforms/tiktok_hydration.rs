@@ -0,0 +1,260 @@
+use headless_chrome::Tab;
+use serde::{de::DeserializeOwned, Deserialize, Deserializer};
+use serde_json::Value;
+use std::{str::FromStr, time::Duration};
+use tracing::warn;
+
+use crate::{
+  cookies,
+  downloader::{DownloadContext, PlatformDownloader, VideoCodec},
+  downloader_error::DownloaderError,
+  info_json::InfoJson,
+  platforms::tiktok::{self, TiktokDownloader},
+};
+
+const HYDRATION_SCRIPT_ID: &str = "__UNIVERSAL_DATA_FOR_REHYDRATION__";
+const HYDRATION_POLL_INTERVAL: Duration = Duration::from_millis(250);
+const VIDEO_DETAIL_POINTER: &str = "/__DEFAULT_SCOPE__/webapp.video-detail/itemInfo/itemStruct";
+const MUSIC_DETAIL_POINTER: &str = "/__DEFAULT_SCOPE__/webapp.music-detail/musicInfo/music";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ItemStruct {
+  pub id: Option<String>,
+  pub desc: Option<String>,
+  #[serde(default, deserialize_with = "lenient_number")]
+  pub create_time: Option<i64>,
+  pub author: Option<Author>,
+  pub stats: Option<Stats>,
+  pub video: Option<Video>,
+  pub image_post: Option<ImagePost>,
+  pub music: Option<Music>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Author {
+  pub unique_id: Option<String>,
+  pub nickname: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Stats {
+  #[serde(default, deserialize_with = "lenient_number")]
+  pub digg_count: Option<u64>,
+  #[serde(default, deserialize_with = "lenient_number")]
+  pub share_count: Option<u64>,
+  #[serde(default, deserialize_with = "lenient_number")]
+  pub comment_count: Option<u64>,
+  #[serde(default, deserialize_with = "lenient_number")]
+  pub play_count: Option<u64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Video {
+  #[serde(default)]
+  pub bitrate_info: Vec<BitrateInfo>,
+}
+
+#[derive(Deserialize)]
+pub struct ImagePost {
+  #[serde(default)]
+  pub images: Vec<Image>,
+}
+
+#[derive(Deserialize)]
+pub struct Image {
+  #[serde(rename = "imageURL")]
+  pub image_url: UrlList,
+}
+
+#[derive(Deserialize)]
+pub struct UrlList {
+  #[serde(rename = "urlList", default)]
+  pub url_list: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Music {
+  #[serde(default, deserialize_with = "lenient_number")]
+  pub id: Option<u64>,
+  pub title: Option<String>,
+  pub author_name: Option<String>,
+  pub play_url: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BitrateInfo {
+  pub bitrate: Option<u64>,
+  pub codec_type: Option<String>,
+  pub gear_name: Option<String>,
+  pub play_addr: PlayAddr,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PlayAddr {
+  #[serde(default)]
+  pub url_list: Vec<String>,
+  pub width: Option<u64>,
+  pub height: Option<u64>,
+}
+
+impl ItemStruct {
+  pub fn bitrate_info(&self) -> &[BitrateInfo] {
+    self.video.as_ref().map(|video| video.bitrate_info.as_slice()).unwrap_or_default()
+  }
+
+  pub fn images(&self) -> &[Image] {
+    self.image_post.as_ref().map(|image_post| image_post.images.as_slice()).unwrap_or_default()
+  }
+
+  pub fn music_url(&self) -> Option<&str> {
+    self.music.as_ref().and_then(|music| music.url())
+  }
+
+  pub fn canonical_url(&self) -> Option<String> {
+    let kind = if self.images().is_empty() { "video" } else { "photo" };
+    Some(format!("https://www.tiktok.com/@{}/{kind}/{}", self.author.as_ref()?.unique_id.as_ref()?, self.id.as_ref()?))
+  }
+
+  pub fn info(&self, url: &str) -> InfoJson {
+    let author = self.author.as_ref();
+    let stats = self.stats.as_ref();
+    InfoJson {
+      text: self.desc.clone().filter(|desc| !desc.is_empty()),
+      uploader: author.and_then(|author| author.unique_id.clone()),
+      uploader_name: author.and_then(|author| author.nickname.clone()),
+      timestamp: self.create_time,
+      like_count: stats.and_then(|stats| stats.digg_count),
+      repost_count: stats.and_then(|stats| stats.share_count),
+      reply_count: stats.and_then(|stats| stats.comment_count),
+      view_count: stats.and_then(|stats| stats.play_count),
+      ..InfoJson::new(TiktokDownloader::NAME, self.id.clone().or_else(|| TiktokDownloader::extract_id(url)), url)
+    }
+  }
+}
+
+impl Music {
+  pub fn url(&self) -> Option<&str> {
+    self.play_url.as_deref().filter(|url| !url.is_empty())
+  }
+}
+
+impl Image {
+  pub fn url(&self) -> Option<&str> {
+    self.image_url.url_list.first().map(|url| url.as_str())
+  }
+}
+
+impl BitrateInfo {
+  //bytevc1 is tiktok's name for its h.265 encodes
+  pub fn codec(&self) -> Option<VideoCodec> {
+    let codec_type = self.codec_type.as_deref()?.to_lowercase();
+    match codec_type {
+      _ if codec_type.contains("h264") => Some(VideoCodec::H264),
+      _ if codec_type.contains("h265") || codec_type.contains("bytevc1") || codec_type.contains("hevc") => Some(VideoCodec::H265),
+      _ => None,
+    }
+  }
+
+  pub fn resolution(&self) -> Option<String> {
+    Some(format!("{}x{}", self.play_addr.width?, self.play_addr.height?))
+  }
+
+  pub fn pixels(&self) -> u64 {
+    self.play_addr.width.unwrap_or(0) * self.play_addr.height.unwrap_or(0)
+  }
+
+  pub fn url(&self) -> Option<&str> {
+    self.play_addr.url_list.first().map(|url| url.as_str())
+  }
+}
+
+pub fn parse_item(json: &str) -> Result<ItemStruct, DownloaderError> {
+  parse_pointer(json, VIDEO_DETAIL_POINTER)
+}
+
+pub fn parse_music(json: &str) -> Result<Music, DownloaderError> {
+  parse_pointer(json, MUSIC_DETAIL_POINTER)
+}
+
+fn parse_pointer<T: DeserializeOwned>(json: &str, pointer: &str) -> Result<T, DownloaderError> {
+  let data: Value = serde_json::from_str(json).map_err(|e| DownloaderError::OtherError(e.to_string()))?;
+  let detail = data.pointer(pointer).ok_or(DownloaderError::FetchError)?;
+  serde_json::from_value(detail.clone()).map_err(|e| DownloaderError::OtherError(e.to_string()))
+}
+
+pub async fn resolve_item(ctx: &DownloadContext, url: &str) -> Result<ItemStruct, DownloaderError> {
+  resolve(ctx, url, parse_item).await
+}
+
+pub async fn resolve_music(ctx: &DownloadContext, url: &str) -> Result<Music, DownloaderError> {
+  resolve(ctx, url, parse_music).await
+}
+
+//the plain page is enough most of the time, the browser passes the checks that sometimes block it
+//the play urls only work with the cookies the page was served with, both ways leave them in the client's cookie jar
+async fn resolve<T>(ctx: &DownloadContext, url: &str, parse: fn(&str) -> Result<T, DownloaderError>) -> Result<T, DownloaderError> {
+  match fetch_hydration(ctx, url).await.and_then(|hydration| parse(&hydration)) {
+    Ok(parsed) => Ok(parsed),
+    Err(e) => {
+      warn!("Could not read the page data without a browser, loading the page instead: {url} ({e})");
+      parse(&load_hydration(ctx, url).await?)
+    }
+  }
+}
+
+async fn fetch_hydration(ctx: &DownloadContext, url: &str) -> Result<String, DownloaderError> {
+  let response = ctx.get(url, tiktok::media_headers()).send().await.map_err(|_| DownloaderError::FetchError)?;
+  if !response.status().is_success() {
+    return Err(DownloaderError::FetchError);
+  }
+
+  let html = response.text().await.map_err(|_| DownloaderError::FetchError)?;
+  extract_hydration(&html).ok_or(DownloaderError::FetchError)
+}
+
+async fn load_hydration(ctx: &DownloadContext, url: &str) -> Result<String, DownloaderError> {
+  let tab = ctx.browser.open_tab().await?;
+  tab.navigate_to(url)?;
+  //polled without blocking the runtime, other downloads of a batch keep running meanwhile
+  let hydration = tokio::time::timeout(ctx.timeout, async {
+    loop {
+      match read_hydration(&tab) {
+        Some(hydration) => return hydration,
+        None => tokio::time::sleep(HYDRATION_POLL_INTERVAL).await,
+      }
+    }
+  })
+  .await
+  .map_err(|_| DownloaderError::FetchError)?;
+  for cookie in tab.get_cookies().unwrap_or_default() {
+    cookies::add_browser_cookie(&ctx.cookie_jar, &cookie);
+  }
+
+  Ok(hydration)
+}
+
+pub fn extract_hydration(html: &str) -> Option<String> {
+  let script_regex = regex::Regex::new(&format!(r#"(?s)<script[^>]*id="{HYDRATION_SCRIPT_ID}"[^>]*>(.*?)</script>"#)).unwrap();
+  script_regex.captures(html).and_then(|captures| captures.get(1)).map(|json| json.as_str().to_string())
+}
+
+pub fn read_hydration(tab: &Tab) -> Option<String> {
+  let hydration_script = format!("document.getElementById('{HYDRATION_SCRIPT_ID}')?.textContent");
+  tab.evaluate(&hydration_script, false).ok().and_then(|result| result.value).and_then(|value| value.as_str().map(|value| value.to_string()))
+}
+
+//tiktok serializes some numbers as strings depending on the page version
+pub fn lenient_number<'de, D: Deserializer<'de>, T: FromStr>(deserializer: D) -> Result<Option<T>, D::Error> {
+  Ok(match Value::deserialize(deserializer)? {
+    Value::Number(number) => number.to_string().parse().ok(),
+    Value::String(string) => string.parse().ok(),
+    _ => None,
+  })
+}
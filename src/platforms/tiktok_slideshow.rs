@@ -0,0 +1,89 @@
+use std::{collections::HashMap, path::Path};
+use tokio::process::Command;
+
+use crate::{
+  downloader::DownloadContext,
+  downloader_error::DownloaderError,
+  format::Format,
+  info_json,
+  platforms::{tiktok, tiktok_hydration::ItemStruct},
+};
+
+const SECONDS_PER_IMAGE: u32 = 3;
+const SLIDESHOW_FILTER: &str = "scale=1080:1920:force_original_aspect_ratio=decrease,pad=1080:1920:(ow-iw)/2:(oh-ih)/2,setsar=1,format=yuv420p";
+
+//images are saved in order with an index, the background sound next to them
+pub async fn download(ctx: &DownloadContext, url: &str, item: &ItemStruct, fields: HashMap<&str, String>) -> Result<Vec<String>, DownloaderError> {
+  let image_urls: Vec<&str> = item.images().iter().filter_map(|image| image.url()).collect();
+  if image_urls.is_empty() {
+    return Err(DownloaderError::NoMediaError);
+  }
+
+  let mut image_names = vec![];
+  for (index, image_url) in image_urls.iter().enumerate() {
+    let mut fields = fields.clone();
+    fields.insert("index", (index + 1).to_string());
+    fields.insert("ext", tiktok::url_extension(image_url, "jpg"));
+    let image_name = ctx.output_path(&fields).await?;
+
+    let bytes = tiktok::fetch_media(ctx, image_url).await?;
+    tokio::fs::write(&image_name, bytes).await.map_err(|_| DownloaderError::IOError)?;
+    image_names.push(image_name);
+  }
+
+  let mut sound_name = None;
+  if let Some(music_url) = item.music_url() {
+    let mut fields = fields.clone();
+    fields.insert("ext", tiktok::url_extension(music_url, "mp3"));
+    let output_name = ctx.output_path(&fields).await?;
+
+    let bytes = tiktok::fetch_media(ctx, music_url).await?;
+    tokio::fs::write(&output_name, bytes).await.map_err(|_| DownloaderError::IOError)?;
+    sound_name = Some(output_name);
+  }
+
+  let mut outputs = image_names.clone();
+  outputs.extend(sound_name.clone());
+  if ctx.settings.slideshow_video {
+    let mut fields = fields.clone();
+    fields.insert("ext", "mp4".to_string());
+    let video_name = ctx.output_path(&fields).await?;
+
+    render_slideshow(ctx, &image_names, sound_name.as_deref(), &video_name).await?;
+    outputs.push(video_name);
+  }
+
+  if ctx.settings.write_info_json {
+    let mut info = item.info(url);
+    info.media_urls = image_urls.iter().map(|image_url| image_url.to_string()).chain(item.music_url().map(|url| url.to_string())).collect();
+    info.format = Some(Format { id: "slideshow".to_string(), resolution: None, url: url.to_string() });
+    //the sidecar belongs to the whole post, so it is named without an image index
+    info_json::write(&ctx.output_path(&fields).await?, &info).await?;
+  }
+
+  Ok(outputs)
+}
+
+//the concat demuxer shows every image for a fixed time, the last entry has to be repeated for its duration to apply
+async fn render_slideshow(ctx: &DownloadContext, image_names: &[String], sound_name: Option<&str>, video_name: &str) -> Result<(), DownloaderError> {
+  let mut concat_list = String::new();
+  for image_name in image_names.iter().chain(image_names.last()) {
+    let image_path = std::fs::canonicalize(image_name).map_err(|_| DownloaderError::IOError)?;
+    concat_list.push_str(&format!("file '{}'\nduration {SECONDS_PER_IMAGE}\n", image_path.to_string_lossy().replace('\'', r"'\''")));
+  }
+  let list_name = Path::new(video_name).with_extension("txt");
+  tokio::fs::write(&list_name, concat_list).await.map_err(|_| DownloaderError::IOError)?;
+
+  let mut command = Command::new(&ctx.settings.ffmpeg_path);
+  command.args(["-f", "concat", "-safe", "0"]).arg("-i").arg(&list_name);
+  if let Some(sound_name) = sound_name {
+    command.args(["-i", sound_name]).args(["-c:a", "aac"]).arg("-shortest");
+  }
+  let output = command.args(["-vf", SLIDESHOW_FILTER]).args(["-c:v", "libx264", "-r", "30"]).arg("-y").arg(video_name).output().await;
+  let _ = tokio::fs::remove_file(&list_name).await;
+
+  match output {
+    Ok(output) if output.status.success() => Ok(()),
+    _ => Err(DownloaderError::FfmpegError),
+  }
+}
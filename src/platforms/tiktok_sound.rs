@@ -0,0 +1,69 @@
+use std::{collections::HashMap, path::Path};
+use tokio::process::Command;
+
+use crate::{
+  downloader::{DownloadContext, PlatformDownloader, SoundFormat},
+  downloader_error::DownloaderError,
+  format::Format,
+  info_json::{self, InfoJson},
+  platforms::{
+    tiktok::{self, TiktokDownloader},
+    tiktok_hydration::Music,
+  },
+};
+
+//the sound is served as it was uploaded, ffmpeg only converts it when the container differs and tags it
+pub async fn download(
+  ctx: &DownloadContext,
+  url: &str,
+  music: &Music,
+  sound_format: SoundFormat,
+  mut fields: HashMap<&str, String>,
+) -> Result<Vec<String>, DownloaderError> {
+  let sound_url = music.url().ok_or(DownloaderError::NoMediaError)?;
+  let source_ext = tiktok::url_extension(sound_url, "mp3");
+  let (ext, codec) = match (sound_format, source_ext.as_str()) {
+    (SoundFormat::M4a, "m4a" | "aac") => ("m4a", "copy"),
+    (SoundFormat::M4a, _) => ("m4a", "aac"),
+    (SoundFormat::Mp3, "mp3") => ("mp3", "copy"),
+    (SoundFormat::Mp3, _) => ("mp3", "libmp3lame"),
+  };
+
+  if let Some(id) = music.id {
+    fields.entry("id").or_insert(id.to_string());
+  }
+  fields.insert("ext", ext.to_string());
+  let output_name = ctx.output_path(&fields).await?;
+  let source_name = Path::new(&output_name).with_extension(format!("source.{source_ext}"));
+
+  let bytes = tiktok::fetch_media(ctx, sound_url).await?;
+  tokio::fs::write(&source_name, bytes).await.map_err(|_| DownloaderError::IOError)?;
+
+  let mut command = Command::new(&ctx.settings.ffmpeg_path);
+  command.arg("-i").arg(&source_name).arg("-vn").args(["-c:a", codec]);
+  if let Some(title) = &music.title {
+    command.args(["-metadata", &format!("title={title}")]);
+  }
+  if let Some(author_name) = &music.author_name {
+    command.args(["-metadata", &format!("artist={author_name}")]);
+  }
+  let output = command.arg("-y").arg(&output_name).output().await;
+  let _ = tokio::fs::remove_file(&source_name).await;
+  match output {
+    Ok(output) if output.status.success() => {}
+    _ => return Err(DownloaderError::FfmpegError),
+  }
+
+  if ctx.settings.write_info_json {
+    let info = InfoJson {
+      text: music.title.clone(),
+      uploader_name: music.author_name.clone(),
+      media_urls: vec![sound_url.to_string()],
+      format: Some(Format { id: format!("sound-{source_ext}"), resolution: None, url: sound_url.to_string() }),
+      ..InfoJson::new(TiktokDownloader::NAME, fields.get("id").cloned(), url)
+    };
+    info_json::write(&output_name, &info).await?;
+  }
+
+  Ok(vec![output_name])
+}
@@ -6,113 +6,487 @@ use headless_chrome::{
   protocol::cdp::{
     Fetch::{events::RequestPausedEvent, RequestPattern, RequestStage},
     Network::ResourceType,
-    Target::CreateTarget,
   },
-  Browser,
 };
-use std::sync::Arc;
-use std::time::Duration;
-use tokio::sync::Mutex;
+use reqwest::header::HeaderMap;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::{process::Command, sync::oneshot};
+use tracing::warn;
 
 use crate::{
-  downloader::{PlatformDownloader, PreferredResolution},
+  downloader::{DownloadContext, GifFormat, PlatformDownloader},
   downloader_error::DownloaderError,
-  playlist::variant_playlist::VariantPlaylist,
+  filename,
+  format::{self, Format},
+  info_json::{self, InfoJson},
+  platforms::{
+    twitter_api::{self, Media, Tweet, Variant},
+    twitter_spaces,
+  },
+  playlist::{master_playlist::MasterPlaylist, variant_playlist::VariantPlaylist},
 };
 
 pub struct TwitterDownloader {}
 
 impl PlatformDownloader for TwitterDownloader {
-  async fn download(browser: Arc<Browser>, url: &str, preferred_resolution: Option<PreferredResolution>) -> Result<String, DownloaderError> {
-    let target = get_initial_tab_create_target();
-    let tab = browser.new_tab_with_options(target)?;
-    let intercepted_url = Arc::new(Mutex::new(String::new()));
-    let interceptor = get_interceptor(intercepted_url.clone());
+  const NAME: &'static str = "twitter";
+  const COOKIE_DOMAINS: &'static [&'static str] = &["x.com", "twitter.com"];
+
+  async fn download(ctx: &DownloadContext, url: &str) -> Result<Vec<String>, DownloaderError> {
+    let mut fields = HashMap::from([("platform", Self::NAME.to_string())]);
+    if let Some(space_id) = twitter_spaces::extract_space_id(url) {
+      fields.insert("id", space_id.clone());
+      return twitter_spaces::download(ctx, url, &space_id, fields).await;
+    }
+
+    let id = Self::extract_id(url);
+    if let Some(id) = &id {
+      fields.insert("id", id.clone());
 
-    tab.enable_fetch(Some(&vec![get_request_pattern()]), None)?;
-    tab.enable_request_interception(interceptor)?;
-    tab.navigate_to(url)?;
+      if ctx.settings.thread {
+        match collect_thread(ctx, id).await {
+          Ok(chain) => return download_thread(ctx, &thread_tweets(ctx, &chain), fields).await,
+          Err(e) => warn!("Could not fetch the thread of tweet {id}, downloading only the tweet itself ({e})"),
+        }
+      }
 
-    let mut found = false;
-    let mut timeout = 10.0 as f32;
-    while !found && timeout >= 0.0 {
-      found = !intercepted_url.lock().await.is_empty();
-      tokio::time::sleep(Duration::from_millis(100)).await;
-      timeout -= 0.1;
+      //videos of quoted tweets and cards are not part of the tweet's own media, the player in the browser still plays them
+      match twitter_api::fetch_tweet(ctx, id).await {
+        Ok(tweet) => match download_tweet(ctx, &tweet, url, fields.clone()).await {
+          Err(DownloaderError::NoMediaError) => warn!("Tweet {id} has no media of its own in the api, falling back to the browser"),
+          result => return result,
+        },
+        Err(e) => warn!("Could not fetch tweet {id} from the api, falling back to the browser ({e})"),
+      }
     }
-    let _ = tab.close(false);
-    if !found {
-      return Err(DownloaderError::FetchError);
+
+    let media_url = intercept_media_url(ctx, url).await?;
+    let (output, format) = if is_gif_url(&media_url) {
+      download_gif(ctx, &media_url, fields).await?
+    } else {
+      let mut variant_playlist = load_variant_playlist(ctx, &media_url).await?;
+      download_hls(ctx, &mut variant_playlist, fields).await?
+    };
+    if ctx.settings.write_info_json {
+      let info = InfoJson { media_urls: vec![media_url], ..InfoJson::new(Self::NAME, id, url) };
+      info_json::write(&output, &info.with_format(format)).await?;
     }
 
-    let variant_playlist_url = intercepted_url.lock().await.to_owned();
-    let mut variant_playlist = VariantPlaylist::from_url(&variant_playlist_url)
-      .await
-      .map_err(|_| DownloaderError::FetchError)?;
+    Ok(vec![output])
+  }
 
-    if variant_playlist.master_playlists.is_empty() {
-      return Err(DownloaderError::NoMasterPlaylistError);
+  async fn list_formats(ctx: &DownloadContext, url: &str) -> Result<Vec<Format>, DownloaderError> {
+    if let Some(space_id) = twitter_spaces::extract_space_id(url) {
+      return twitter_spaces::list_formats(ctx, &space_id).await;
     }
 
-    let resolution_index = match preferred_resolution {
-      None | Some(PreferredResolution::High) => 0,
-      Some(PreferredResolution::Medium) => variant_playlist.master_playlists.len() / 2,
-      Some(PreferredResolution::Low) => variant_playlist.master_playlists.len() - 1,
-    };
+    if let Some(id) = Self::extract_id(url) {
+      match twitter_api::fetch_tweet(ctx, &id).await {
+        Ok(tweet) => {
+          let formats = tweet
+            .media()
+            .iter()
+            .filter(|media| media.is_video())
+            .flat_map(|media| media.mp4_variants())
+            .map(|variant| variant_format(&variant))
+            .collect();
 
-    variant_playlist.master_playlists[resolution_index].download().await
+          return Ok(formats);
+        }
+        Err(e) => warn!("Could not fetch tweet {id} from the api, falling back to the browser ({e})"),
+      }
+    }
+
+    let media_url = intercept_media_url(ctx, url).await?;
+    if is_gif_url(&media_url) {
+      return Ok(vec![Format { id: "gif-mp4".to_string(), resolution: None, url: media_url }]);
+    }
+
+    let variant_playlist = load_variant_playlist(ctx, &media_url).await?;
+
+    let formats = variant_playlist.master_playlists.iter().map(hls_format).collect();
+
+    Ok(formats)
   }
 
   fn validate_url(url: &str) -> Result<(), DownloaderError> {
     let twitter_regex = regex::Regex::new(r"https:\/\/(www\.)?(twitter|x).com\/.+\/status\/\d+(\?.*)?").unwrap();
 
-    if !twitter_regex.is_match(url) {
+    if !twitter_regex.is_match(url) && twitter_spaces::extract_space_id(url).is_none() {
       return Err(DownloaderError::UnsupportedPlatformError);
     }
 
     Ok(())
   }
+
+  fn extract_id(url: &str) -> Option<String> {
+    if let Some(space_id) = twitter_spaces::extract_space_id(url) {
+      return Some(space_id);
+    }
+
+    let id_regex = regex::Regex::new(r"\/status\/(?P<id>\d+)").unwrap();
+    id_regex.captures(url).and_then(|captures| captures.name("id")).map(|id| id.as_str().to_string())
+  }
+
+  fn is_collection_url(_ctx: &DownloadContext, url: &str) -> bool {
+    extract_screen_name(url).is_some()
+  }
+
+  //the media timeline is newest first, so paging stops at the first tweet older than the date range
+  async fn list_collection(ctx: &DownloadContext, url: &str) -> Result<Vec<String>, DownloaderError> {
+    let screen_name = extract_screen_name(url).ok_or(DownloaderError::InvalidInputError)?;
+    let user_id = twitter_api::fetch_user_id(ctx, &screen_name).await?;
+
+    let filter = &ctx.settings.collection_filter;
+    let mut post_urls = vec![];
+    let mut cursor: Option<String> = None;
+    loop {
+      let page = twitter_api::fetch_user_media(ctx, &user_id, cursor.as_deref()).await?;
+      if page.tweets.is_empty() {
+        break;
+      }
+
+      for tweet in &page.tweets {
+        //a tweet without a readable date can't be placed in the range, it is skipped instead of ending the listing
+        match tweet.timestamp() {
+          Some(timestamp) if filter.is_too_old(timestamp) => return Ok(post_urls),
+          Some(timestamp) if filter.is_too_new(timestamp) => continue,
+          None if filter.has_date_range() => continue,
+          _ => {}
+        }
+        if tweet.media().is_empty() {
+          continue;
+        }
+
+        post_urls.push(format!("https://x.com/{screen_name}/status/{}", tweet.rest_id));
+        if filter.is_full(post_urls.len()) {
+          return Ok(post_urls);
+        }
+      }
+
+      match page.cursor {
+        Some(next_cursor) if cursor.as_ref() != Some(&next_cursor) => cursor = Some(next_cursor),
+        _ => break,
+      }
+    }
+
+    Ok(post_urls)
+  }
 }
 
-fn get_interceptor(intercepted_url: Arc<Mutex<String>>) -> Arc<dyn RequestInterceptor + Send + Sync> {
-  Arc::new(move |_transport: Arc<Transport>, _session_id: SessionId, event: RequestPausedEvent| {
-    let request = event.params.request.clone();
+//x.com/<user> and x.com/<user>/media, excluding the site's own pages
+fn extract_screen_name(url: &str) -> Option<String> {
+  const RESERVED_PATHS: [&str; 10] = ["home", "explore", "search", "i", "settings", "notifications", "messages", "compose", "login", "hashtag"];
+
+  let profile_regex = regex::Regex::new(r"^https:\/\/(www\.)?(twitter|x)\.com\/(?P<screen_name>\w{1,15})(\/media)?\/?(\?.*)?$").unwrap();
+  let screen_name = profile_regex.captures(url)?.name("screen_name")?.as_str();
+  if RESERVED_PATHS.iter().any(|reserved| reserved.eq_ignore_ascii_case(screen_name)) {
+    return None;
+  }
+
+  Some(screen_name.to_string())
+}
 
-    if request.url.contains("tag=") && intercepted_url.blocking_lock().is_empty() {
-      let mut mutex_guard = intercepted_url.blocking_lock();
-      let pure_url = match request.url.find('?') {
-        Some(index) => request.url[..index].to_string(),
-        None => request.url,
-      };
+async fn download_tweet(ctx: &DownloadContext, tweet: &Tweet, url: &str, fields: HashMap<&str, String>) -> Result<Vec<String>, DownloaderError> {
+  let info = tweet_info(tweet, url);
+  let selected_media: Vec<&Media> =
+    tweet.media().iter().filter(|media| media.is_video() || (ctx.settings.include_images && media.is_photo())).collect();
+  if selected_media.is_empty() {
+    return Err(DownloaderError::NoMediaError);
+  }
 
-      *mutex_guard = pure_url;
+  let mut outputs = vec![];
+  for (index, media) in selected_media.iter().enumerate() {
+    let mut fields = fields.clone();
+    if selected_media.len() > 1 {
+      fields.insert("index", (index + 1).to_string());
     }
+    let (output, format) = download_media(ctx, media, fields).await?;
+    if ctx.settings.write_info_json {
+      info_json::write(&output, &info.with_format(format)).await?;
+    }
+    outputs.push(output);
+  }
 
-    RequestPausedDecision::Continue(None)
-  })
+  Ok(outputs)
+}
+
+//tweets of the thread without media are skipped but still count towards the position
+async fn download_thread(ctx: &DownloadContext, thread: &[&Tweet], fields: HashMap<&str, String>) -> Result<Vec<String>, DownloaderError> {
+  let mut outputs = vec![];
+  for (position, tweet) in thread.iter().enumerate() {
+    let mut fields = fields.clone();
+    fields.insert("id", tweet.rest_id.clone());
+    //zero padded so the files of a thread sort in order
+    fields.insert("thread_position", format!("{:03}", position + 1));
+
+    match download_tweet(ctx, tweet, &tweet_url(tweet), fields).await {
+      Ok(tweet_outputs) => outputs.extend(tweet_outputs),
+      Err(DownloaderError::NoMediaError) => {}
+      Err(e) => return Err(e),
+    }
+  }
+
+  if outputs.is_empty() {
+    return Err(DownloaderError::NoMediaError);
+  }
+  Ok(outputs)
+}
+
+//walks up and down the author's replies to themselves, fetching more of the conversation when the chain reaches its end
+async fn collect_thread(ctx: &DownloadContext, id: &str) -> Result<Vec<Tweet>, DownloaderError> {
+  let mut tweets: HashMap<String, Tweet> = HashMap::new();
+  for tweet in twitter_api::fetch_conversation(ctx, id).await? {
+    tweets.insert(tweet.rest_id.clone(), tweet);
+  }
+  let author = tweets.get(id).and_then(|tweet| tweet.legacy.user_id_str.clone()).ok_or(DownloaderError::FetchError)?;
+  let is_self_reply = |tweet: &Tweet, parent_id: &str| {
+    tweet.legacy.user_id_str.as_ref() == Some(&author) && tweet.legacy.in_reply_to_status_id_str.as_deref() == Some(parent_id)
+  };
+
+  let mut chain = vec![id.to_string()];
+  while let Some(parent_id) = tweets.get(&chain[0]).and_then(|tweet| tweet.legacy.in_reply_to_status_id_str.clone()) {
+    match tweets.get(&parent_id) {
+      Some(parent) if parent.legacy.user_id_str.as_ref() == Some(&author) => chain.insert(0, parent_id),
+      _ => break,
+    }
+  }
+
+  let mut last_fetched = id.to_string();
+  loop {
+    let last = chain.last().unwrap().clone();
+    let next = tweets
+      .values()
+      .filter(|tweet| is_self_reply(tweet, &last))
+      .map(|tweet| tweet.rest_id.clone())
+      .min_by_key(|id| id.parse::<u64>().unwrap_or(u64::MAX));
+    if let Some(next) = next {
+      chain.push(next);
+      continue;
+    }
+    if last == last_fetched {
+      break;
+    }
+
+    for tweet in twitter_api::fetch_conversation(ctx, &last).await? {
+      tweets.entry(tweet.rest_id.clone()).or_insert(tweet);
+    }
+    last_fetched = last;
+  }
+
+  Ok(chain.iter().filter_map(|id| tweets.remove(id)).collect())
+}
+
+fn thread_tweets<'a>(ctx: &DownloadContext, chain: &'a [Tweet]) -> Vec<&'a Tweet> {
+  chain.iter().flat_map(|tweet| std::iter::once(tweet).chain(tweet.quoted_tweet().filter(|_| ctx.settings.thread_quotes))).collect()
+}
+
+fn tweet_url(tweet: &Tweet) -> String {
+  format!("https://x.com/{}/status/{}", tweet.author_handle().unwrap_or_else(|| "i".to_string()), tweet.rest_id)
+}
+
+fn tweet_info(tweet: &Tweet, url: &str) -> InfoJson {
+  InfoJson {
+    text: Some(tweet.legacy.full_text.clone()),
+    uploader: tweet.author_handle(),
+    uploader_name: tweet.author_name(),
+    timestamp: tweet.timestamp(),
+    like_count: tweet.legacy.favorite_count,
+    repost_count: tweet.legacy.retweet_count,
+    reply_count: tweet.legacy.reply_count,
+    quote_count: tweet.legacy.quote_count,
+    view_count: tweet.view_count(),
+    media_urls: tweet.media().iter().filter_map(|media| media.source_url()).collect(),
+    ..InfoJson::new(TwitterDownloader::NAME, Some(tweet.rest_id.clone()), url)
+  }
+}
+
+fn variant_format(variant: &Variant) -> Format {
+  Format { id: format!("http-{}", variant.bitrate.unwrap_or(0) / 1000), resolution: variant.resolution(), url: variant.url.clone() }
+}
+
+fn hls_format(master_playlist: &MasterPlaylist) -> Format {
+  Format {
+    id: format!("hls-{}", master_playlist.resolution),
+    resolution: Some(master_playlist.resolution.clone()),
+    url: master_playlist.video_media_url.clone(),
+  }
+}
+
+async fn download_media(ctx: &DownloadContext, media: &Media, fields: HashMap<&str, String>) -> Result<(String, Format), DownloaderError> {
+  if media.is_photo() {
+    return download_photo(ctx, media, fields).await;
+  }
+
+  let variants = media.mp4_variants();
+  if media.is_gif() && !variants.is_empty() {
+    return download_gif(ctx, &variants[0].url, fields).await;
+  }
+  if !variants.is_empty() {
+    let variant = &variants[format::preferred_index(&ctx.preferred_resolution, variants.len())];
+    return download_variant(ctx, variant, fields).await;
+  }
+
+  let Some(hls_url) = media.hls_url() else {
+    return Err(DownloaderError::NoMediaError);
+  };
+  let mut variant_playlist = load_variant_playlist(ctx, hls_url).await?;
+  download_hls(ctx, &mut variant_playlist, fields).await
+}
+
+async fn download_hls(
+  ctx: &DownloadContext,
+  variant_playlist: &mut VariantPlaylist,
+  fields: HashMap<&str, String>,
+) -> Result<(String, Format), DownloaderError> {
+  let resolution_index = format::preferred_index(&ctx.preferred_resolution, variant_playlist.master_playlists.len());
+  let master_playlist = &mut variant_playlist.master_playlists[resolution_index];
+  let format = hls_format(master_playlist);
+  Ok((master_playlist.download(ctx, fields).await?, format))
+}
+
+async fn download_photo(ctx: &DownloadContext, media: &Media, mut fields: HashMap<&str, String>) -> Result<(String, Format), DownloaderError> {
+  let file_name = media.media_url_https.split('/').next_back().unwrap_or_default();
+  let (photo_name, ext) = file_name.rsplit_once('.').unwrap_or((file_name, "jpg"));
+  fields.insert("name", filename::sanitize(photo_name));
+  fields.insert("ext", ext.to_string());
+  let output_name = ctx.output_path(&fields).await?;
+
+  let photo_url = media.original_photo_url();
+  download_file(ctx, &photo_url, &output_name).await?;
+  Ok((output_name, Format { id: "orig".to_string(), resolution: None, url: photo_url }))
+}
+
+async fn download_variant(ctx: &DownloadContext, variant: &Variant, mut fields: HashMap<&str, String>) -> Result<(String, Format), DownloaderError> {
+  let resolution = variant.resolution().unwrap_or_default();
+  let file_name = variant.url.split('?').next().unwrap_or_default().split('/').next_back().unwrap_or_default();
+  let video_name = filename::sanitize(file_name.split('.').next().unwrap_or_default());
+  fields.insert("name", format!("{}_{}", video_name, resolution));
+  fields.insert("resolution", resolution);
+  fields.insert("ext", "mp4".to_string());
+  let output_name = ctx.output_path(&fields).await?;
+
+  download_file(ctx, &variant.url, &output_name).await?;
+  Ok((output_name, variant_format(variant)))
+}
+
+//gifs are only served as a single progressive mp4, which can optionally be transcoded to an actual animation format
+async fn download_gif(ctx: &DownloadContext, url: &str, mut fields: HashMap<&str, String>) -> Result<(String, Format), DownloaderError> {
+  let file_name = url.split('?').next().unwrap_or_default().split('/').next_back().unwrap_or_default();
+  fields.insert("name", filename::sanitize(file_name.split('.').next().unwrap_or_default()));
+  fields.insert("ext", "mp4".to_string());
+  let video_name = ctx.output_path(&fields).await?;
+
+  download_file(ctx, url, &video_name).await?;
+  let mut format = Format { id: "gif-mp4".to_string(), resolution: None, url: url.to_string() };
+
+  let filters = match ctx.settings.gif_format {
+    GifFormat::Mp4 => return Ok((video_name, format)),
+    GifFormat::Gif => ["-vf", "fps=15,split[s0][s1];[s0]palettegen=stats_mode=diff[p];[s1][p]paletteuse=dither=bayer"],
+    GifFormat::Webp => ["-vcodec", "libwebp"],
+  };
+  let ext = match ctx.settings.gif_format {
+    GifFormat::Webp => "webp",
+    _ => "gif",
+  };
+  fields.insert("ext", ext.to_string());
+  let output_name = ctx.output_path(&fields).await?;
+  format.id = format!("gif-{ext}");
+
+  let output = Command::new(&ctx.settings.ffmpeg_path)
+    .args(["-i", &video_name])
+    .args(filters)
+    .args(["-loop", "0"])
+    .arg("-an")
+    .arg("-y")
+    .arg(&output_name)
+    .output()
+    .await
+    .map_err(|_| DownloaderError::FfmpegError)?;
+  if !output.status.success() {
+    return Err(DownloaderError::FfmpegError);
+  }
+
+  tokio::fs::remove_file(video_name).await.map_err(|_| DownloaderError::IOError)?;
+
+  Ok((output_name, format))
 }
 
-fn get_request_pattern() -> RequestPattern {
-  RequestPattern {
-    url_pattern: Some("https://video.twimg.com/*_video/*".to_string()),
-    resource_Type: Some(ResourceType::Xhr),
-    request_stage: Some(RequestStage::Request),
+async fn download_file(ctx: &DownloadContext, url: &str, output_name: &str) -> Result<(), DownloaderError> {
+  let response = ctx.get(url, HeaderMap::new()).send().await.map_err(|_| DownloaderError::FetchError)?;
+  if !response.status().is_success() {
+    return Err(DownloaderError::FetchError);
   }
+  let bytes = response.bytes().await.map_err(|_| DownloaderError::FetchError)?;
+
+  tokio::fs::write(output_name, bytes).await.map_err(|_| DownloaderError::IOError)
+}
+
+fn is_gif_url(url: &str) -> bool {
+  url.contains("/tweet_video/")
+}
+
+//resolves to either the variant playlist of a video or the mp4 of a gif
+async fn intercept_media_url(ctx: &DownloadContext, url: &str) -> Result<String, DownloaderError> {
+  let tab = ctx.browser.open_tab().await?;
+  let (sender, receiver) = oneshot::channel();
+  let interceptor = get_interceptor(sender);
+
+  tab.enable_fetch(Some(&get_request_patterns()), None)?;
+  tab.enable_request_interception(interceptor)?;
+  tab.navigate_to(url)?;
+
+  let intercepted = tokio::time::timeout(ctx.timeout, receiver).await;
+  drop(tab);
+  let Ok(Ok(media_url)) = intercepted else {
+    return Err(DownloaderError::FetchError);
+  };
+
+  Ok(media_url)
 }
 
-fn get_initial_tab_create_target() -> CreateTarget {
-  CreateTarget {
-    url: "about::blank".to_string(),
-    width: None,
-    height: None,
-    browser_context_id: None,
-    enable_begin_frame_control: None,
-    new_window: Some(true),
-    background: Some(true),
-    left: None,
-    top: None,
-    window_state: None,
-    for_tab: None,
-    hidden: None,
+async fn load_variant_playlist(ctx: &DownloadContext, variant_playlist_url: &str) -> Result<VariantPlaylist, DownloaderError> {
+  let variant_playlist = VariantPlaylist::from_url(ctx, variant_playlist_url).await.map_err(|_| DownloaderError::FetchError)?;
+
+  if variant_playlist.master_playlists.is_empty() {
+    return Err(DownloaderError::NoMasterPlaylistError);
   }
+
+  Ok(variant_playlist)
+}
+
+fn get_interceptor(sender: oneshot::Sender<String>) -> Arc<dyn RequestInterceptor + Send + Sync> {
+  let sender = Mutex::new(Some(sender));
+  Arc::new(move |_transport: Arc<Transport>, _session_id: SessionId, event: RequestPausedEvent| {
+    let request = event.params.request.clone();
+
+    if request.url.contains("tag=") || is_gif_url(&request.url) {
+      if let Some(sender) = sender.lock().unwrap().take() {
+        let pure_url = match request.url.find('?') {
+          Some(index) => request.url[..index].to_string(),
+          None => request.url,
+        };
+
+        let _ = sender.send(pure_url);
+      }
+    }
+
+    RequestPausedDecision::Continue(None)
+  })
+}
+
+fn get_request_patterns() -> Vec<RequestPattern> {
+  vec![
+    RequestPattern {
+      url_pattern: Some("https://video.twimg.com/*_video/*".to_string()),
+      resource_Type: Some(ResourceType::Xhr),
+      request_stage: Some(RequestStage::Request),
+    },
+    RequestPattern {
+      url_pattern: Some("https://video.twimg.com/tweet_video/*".to_string()),
+      resource_Type: Some(ResourceType::Media),
+      request_stage: Some(RequestStage::Request),
+    },
+  ]
 }
@@ -0,0 +1,596 @@
+use regex::Regex;
+use reqwest::{
+  cookie::{CookieStore, Jar},
+  header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION},
+  StatusCode, Url,
+};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{dates, downloader::DownloadContext, downloader_error::DownloaderError};
+
+//public token used by the twitter web client for logged out requests
+const BEARER_TOKEN: &str = "Bearer AAAAAAAAAAAAAAAAAAAAANRILgAAAAAAnNwIzUejRCOuH5E6I8xnZz4puTs%3D1Zv7ttfk8LF81IUq16cHjhLTvJu4FA33AGWWjCpTnA";
+const GUEST_TOKEN_URL: &str = "https://api.x.com/1.1/guest/activate.json";
+const TWEET_RESULT_URL: &str = "https://api.x.com/graphql/Xl5pC_lBk_gcO2ItU39DQw/TweetResultByRestId";
+const USER_BY_SCREEN_NAME_URL: &str = "https://api.x.com/graphql/32pL5BWe9WKeSK1MoPvFQQ/UserByScreenName";
+const USER_MEDIA_URL: &str = "https://api.x.com/graphql/MOLbHrtk8Ovu7DUNOLcXiA/UserMedia";
+const TWEET_DETAIL_URL: &str = "https://api.x.com/graphql/nBS-WpgA6ZG0CyNHD517JQ/TweetDetail";
+const AUDIO_SPACE_URL: &str = "https://api.x.com/graphql/xVEzTKg_mLTHubK5ayL0SQ/AudioSpaceById";
+const LIVE_VIDEO_STREAM_URL: &str = "https://api.x.com/1.1/live_video_stream/status";
+const USER_MEDIA_PAGE_SIZE: usize = 20;
+const FEATURES: &str = r#"{"creator_subscriptions_tweet_preview_api_enabled":true,"communities_web_enable_tweet_community_results_fetch":true,"c9s_tweet_anatomy_moderator_badge_enabled":true,"articles_preview_enabled":true,"responsive_web_edit_tweet_api_enabled":true,"graphql_is_translatable_rweb_tweet_is_translatable_enabled":true,"view_counts_everywhere_api_enabled":true,"longform_notetweets_consumption_enabled":true,"responsive_web_twitter_article_tweet_consumption_enabled":true,"tweet_awards_web_tipping_enabled":false,"creator_subscriptions_quote_tweet_preview_enabled":false,"freedom_of_speech_not_reach_fetch_enabled":true,"standardized_nudges_misinfo":true,"tweet_with_visibility_results_prefer_gql_limited_actions_policy_enabled":true,"rweb_video_timestamps_enabled":true,"longform_notetweets_rich_text_read_enabled":true,"longform_notetweets_inline_media_enabled":true,"rweb_tipjar_consumption_enabled":true,"responsive_web_graphql_exclude_directive_enabled":true,"verified_phone_label_enabled":false,"responsive_web_graphql_skip_user_profile_image_extensions_enabled":false,"responsive_web_graphql_timeline_navigation_enabled":true,"responsive_web_enhance_cards_enabled":false}"#;
+
+#[derive(Deserialize)]
+struct GuestTokenResponse {
+  guest_token: String,
+}
+
+#[derive(Deserialize)]
+struct TweetResultResponse {
+  data: TweetResultData,
+}
+
+#[derive(Deserialize)]
+struct TweetResultData {
+  #[serde(rename = "tweetResult")]
+  tweet_result: Option<TweetResultWrapper>,
+}
+
+#[derive(Deserialize)]
+struct TweetResultWrapper {
+  result: Option<TweetResult>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "__typename")]
+enum TweetResult {
+  Tweet(Tweet),
+  TweetWithVisibilityResults {
+    tweet: Tweet,
+  },
+  #[serde(other)]
+  Unavailable,
+}
+
+#[derive(Deserialize)]
+struct UserByScreenNameResponse {
+  data: UserByScreenNameData,
+}
+
+#[derive(Deserialize)]
+struct UserByScreenNameData {
+  user: Option<UserIdWrapper>,
+}
+
+#[derive(Deserialize)]
+struct UserIdWrapper {
+  result: Option<UserId>,
+}
+
+#[derive(Deserialize)]
+struct UserId {
+  rest_id: String,
+}
+
+#[derive(Deserialize)]
+struct AudioSpaceResponse {
+  data: AudioSpaceData,
+}
+
+#[derive(Deserialize)]
+struct AudioSpaceData {
+  #[serde(rename = "audioSpace")]
+  audio_space: Option<AudioSpace>,
+}
+
+#[derive(Deserialize)]
+struct AudioSpace {
+  metadata: Option<SpaceMetadata>,
+}
+
+//timestamps are in milliseconds
+#[derive(Deserialize)]
+pub struct SpaceMetadata {
+  pub rest_id: String,
+  pub media_key: String,
+  pub title: Option<String>,
+  pub created_at: Option<i64>,
+  pub started_at: Option<i64>,
+  pub creator_results: Option<UserResults>,
+}
+
+#[derive(Deserialize)]
+struct LiveVideoStreamResponse {
+  source: LiveVideoStreamSource,
+}
+
+#[derive(Deserialize)]
+struct LiveVideoStreamSource {
+  location: String,
+}
+
+pub struct TimelinePage {
+  pub tweets: Vec<Tweet>,
+  pub cursor: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct Tweet {
+  pub rest_id: String,
+  pub core: Option<TweetCore>,
+  pub legacy: TweetLegacy,
+  pub views: Option<Views>,
+  quoted_status_result: Option<Box<TweetResultWrapper>>,
+}
+
+#[derive(Deserialize)]
+pub struct TweetCore {
+  pub user_results: UserResults,
+}
+
+#[derive(Deserialize)]
+pub struct UserResults {
+  pub result: Option<User>,
+}
+
+//newer responses moved the names from legacy to core
+#[derive(Deserialize)]
+pub struct User {
+  pub legacy: Option<UserNames>,
+  pub core: Option<UserNames>,
+}
+
+#[derive(Deserialize)]
+pub struct UserNames {
+  pub screen_name: Option<String>,
+  pub name: Option<String>,
+}
+
+impl User {
+  pub fn screen_name(&self) -> Option<String> {
+    self.names().find_map(|names| names.screen_name.clone())
+  }
+
+  pub fn name(&self) -> Option<String> {
+    self.names().find_map(|names| names.name.clone())
+  }
+
+  fn names(&self) -> impl Iterator<Item = &UserNames> {
+    self.core.iter().chain(self.legacy.iter())
+  }
+}
+
+impl SpaceMetadata {
+  pub fn host(&self) -> Option<&User> {
+    self.creator_results.as_ref().and_then(|creator| creator.result.as_ref())
+  }
+
+  pub fn timestamp(&self) -> Option<i64> {
+    self.started_at.or(self.created_at).map(|millis| millis / 1000)
+  }
+}
+
+#[derive(Deserialize)]
+pub struct TweetLegacy {
+  #[serde(default)]
+  pub full_text: String,
+  #[serde(default)]
+  pub created_at: String,
+  pub favorite_count: Option<u64>,
+  pub retweet_count: Option<u64>,
+  pub reply_count: Option<u64>,
+  pub quote_count: Option<u64>,
+  pub user_id_str: Option<String>,
+  pub in_reply_to_status_id_str: Option<String>,
+  pub extended_entities: Option<ExtendedEntities>,
+}
+
+#[derive(Deserialize)]
+pub struct Views {
+  pub count: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ExtendedEntities {
+  #[serde(default)]
+  pub media: Vec<Media>,
+}
+
+#[derive(Deserialize)]
+pub struct Media {
+  #[serde(rename = "type")]
+  pub kind: String,
+  pub media_url_https: String,
+  pub video_info: Option<VideoInfo>,
+}
+
+#[derive(Deserialize)]
+pub struct VideoInfo {
+  #[serde(default)]
+  pub variants: Vec<Variant>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct Variant {
+  pub content_type: String,
+  pub url: String,
+  pub bitrate: Option<u64>,
+}
+
+impl Tweet {
+  pub fn media(&self) -> &[Media] {
+    self.legacy.extended_entities.as_ref().map(|entities| entities.media.as_slice()).unwrap_or_default()
+  }
+
+  pub fn quoted_tweet(&self) -> Option<&Tweet> {
+    match self.quoted_status_result.as_ref().and_then(|wrapper| wrapper.result.as_ref()) {
+      Some(TweetResult::Tweet(tweet)) | Some(TweetResult::TweetWithVisibilityResults { tweet }) => Some(tweet),
+      _ => None,
+    }
+  }
+
+  pub fn author_handle(&self) -> Option<String> {
+    self.author().and_then(User::screen_name)
+  }
+
+  pub fn author_name(&self) -> Option<String> {
+    self.author().and_then(User::name)
+  }
+
+  pub fn view_count(&self) -> Option<u64> {
+    self.views.as_ref().and_then(|views| views.count.as_ref()).and_then(|count| count.parse().ok())
+  }
+
+  //created_at looks like "Wed Oct 10 20:19:24 +0000 2018"
+  pub fn timestamp(&self) -> Option<i64> {
+    let [_, month, day, time, offset, year] = self.legacy.created_at.split_whitespace().collect::<Vec<&str>>()[..] else {
+      return None;
+    };
+
+    const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+    let month = MONTHS.iter().position(|name| *name == month)? as i64 + 1;
+    let day: i64 = day.parse().ok()?;
+    let year: i64 = year.parse().ok()?;
+    let [hours, minutes, seconds] = time.split(':').map(|part| part.parse::<i64>().ok()).collect::<Option<Vec<i64>>>()?[..] else {
+      return None;
+    };
+    let offset: i64 = offset.parse().ok()?;
+    let offset_seconds = (offset / 100) * 3600 + (offset % 100) * 60;
+
+    Some(dates::days_from_civil(year, month, day) * dates::SECONDS_PER_DAY + hours * 3600 + minutes * 60 + seconds - offset_seconds)
+  }
+
+  fn author(&self) -> Option<&User> {
+    self.core.as_ref().and_then(|core| core.user_results.result.as_ref())
+  }
+}
+
+impl Media {
+  pub fn is_video(&self) -> bool {
+    self.kind == "video" || self.kind == "animated_gif"
+  }
+
+  pub fn is_gif(&self) -> bool {
+    self.kind == "animated_gif"
+  }
+
+  pub fn is_photo(&self) -> bool {
+    self.kind == "photo"
+  }
+
+  //name=orig serves the photo as it was uploaded instead of a resized copy
+  pub fn original_photo_url(&self) -> String {
+    match self.media_url_https.rsplit_once('.') {
+      Some((base, ext)) => format!("{base}?format={ext}&name=orig"),
+      None => format!("{}?name=orig", self.media_url_https),
+    }
+  }
+
+  //progressive mp4 variants sorted by bitrate descending
+  pub fn mp4_variants(&self) -> Vec<Variant> {
+    let mut variants: Vec<Variant> =
+      self.video_info.iter().flat_map(|info| info.variants.iter()).filter(|variant| variant.content_type == "video/mp4").cloned().collect();
+    variants.sort_by_key(|variant| std::cmp::Reverse(variant.bitrate.unwrap_or(0)));
+    variants
+  }
+
+  pub fn source_url(&self) -> Option<String> {
+    match self.is_photo() {
+      true => Some(self.original_photo_url()),
+      false => self.mp4_variants().first().map(|variant| variant.url.clone()).or(self.hls_url().map(|url| url.to_string())),
+    }
+  }
+
+  pub fn hls_url(&self) -> Option<&str> {
+    self
+      .video_info
+      .iter()
+      .flat_map(|info| info.variants.iter())
+      .find(|variant| variant.content_type == "application/x-mpegURL")
+      .map(|variant| variant.url.as_str())
+  }
+}
+
+impl Variant {
+  pub fn resolution(&self) -> Option<String> {
+    let resolution_regex = Regex::new(r"\/(?P<resolution>\d+x\d+)\/").unwrap();
+    resolution_regex.captures(&self.url).and_then(|captures| captures.name("resolution")).map(|resolution| resolution.as_str().to_string())
+  }
+}
+
+pub async fn fetch_tweet(ctx: &DownloadContext, id: &str) -> Result<Tweet, DownloaderError> {
+  let variables = format!(r#"{{"tweetId":"{id}","withCommunity":false,"includePromotedContent":false,"withVoice":true}}"#);
+  let body = graphql(ctx, TWEET_RESULT_URL, &variables).await?;
+  parse_tweet(&body)
+}
+
+pub async fn fetch_user_id(ctx: &DownloadContext, screen_name: &str) -> Result<String, DownloaderError> {
+  let variables = format!(r#"{{"screen_name":"{screen_name}","withSafetyModeUserFields":true}}"#);
+  let body = graphql(ctx, USER_BY_SCREEN_NAME_URL, &variables).await?;
+
+  let response: UserByScreenNameResponse = serde_json::from_str(&body).map_err(|e| DownloaderError::OtherError(e.to_string()))?;
+  response.data.user.and_then(|user| user.result).map(|user| user.rest_id).ok_or(DownloaderError::FetchError)
+}
+
+pub async fn fetch_user_media(ctx: &DownloadContext, user_id: &str, cursor: Option<&str>) -> Result<TimelinePage, DownloaderError> {
+  let cursor = cursor.map(|cursor| format!(r#","cursor":"{cursor}""#)).unwrap_or_default();
+  let variables = format!(
+    r#"{{"userId":"{user_id}","count":{USER_MEDIA_PAGE_SIZE}{cursor},"includePromotedContent":false,"withClientEventToken":false,"withBirdwatchNotes":false,"withVoice":true}}"#
+  );
+  let body = graphql(ctx, USER_MEDIA_URL, &variables).await?;
+  parse_timeline(&body)
+}
+
+pub fn parse_tweet(json: &str) -> Result<Tweet, DownloaderError> {
+  let response: TweetResultResponse = serde_json::from_str(json).map_err(|e| DownloaderError::OtherError(e.to_string()))?;
+
+  match response.data.tweet_result.and_then(|wrapper| wrapper.result) {
+    Some(TweetResult::Tweet(tweet)) | Some(TweetResult::TweetWithVisibilityResults { tweet }) => Ok(tweet),
+    _ => Err(DownloaderError::FetchError),
+  }
+}
+
+pub async fn fetch_space(ctx: &DownloadContext, id: &str) -> Result<SpaceMetadata, DownloaderError> {
+  let variables = format!(r#"{{"id":"{id}","isMetatagsQuery":false,"withReplays":true,"withListens":true}}"#);
+  let body = graphql(ctx, AUDIO_SPACE_URL, &variables).await?;
+
+  let response: AudioSpaceResponse = serde_json::from_str(&body).map_err(|e| DownloaderError::OtherError(e.to_string()))?;
+  response.data.audio_space.and_then(|space| space.metadata).ok_or(DownloaderError::FetchError)
+}
+
+//only available while the space is live or when its host kept a replay
+pub async fn fetch_space_stream_url(ctx: &DownloadContext, media_key: &str) -> Result<String, DownloaderError> {
+  let body = api_get(ctx, &format!("{LIVE_VIDEO_STREAM_URL}/{media_key}"), &[]).await?;
+  let stream: LiveVideoStreamResponse = serde_json::from_str(&body).map_err(|_| DownloaderError::FetchError)?;
+  Ok(stream.source.location)
+}
+
+//the conversation around a tweet, including the replies shown below it
+pub async fn fetch_conversation(ctx: &DownloadContext, focal_id: &str) -> Result<Vec<Tweet>, DownloaderError> {
+  let variables = format!(
+    r#"{{"focalTweetId":"{focal_id}","with_rux_injections":false,"includePromotedContent":false,"withCommunity":true,"withBirdwatchNotes":true,"withVoice":true,"withV2Timeline":true}}"#
+  );
+  let body = graphql(ctx, TWEET_DETAIL_URL, &variables).await?;
+  Ok(parse_timeline(&body)?.tweets)
+}
+
+//timeline layouts differ between endpoints, so tweets and the bottom cursor are looked up anywhere in the response
+pub fn parse_timeline(json: &str) -> Result<TimelinePage, DownloaderError> {
+  let response: Value = serde_json::from_str(json).map_err(|e| DownloaderError::OtherError(e.to_string()))?;
+
+  let mut page = TimelinePage { tweets: vec![], cursor: None };
+  collect_timeline(&response, &mut page);
+  Ok(page)
+}
+
+fn collect_timeline(value: &Value, page: &mut TimelinePage) {
+  match value {
+    Value::Object(object) => {
+      if let Some(result) = object.get("tweet_results").and_then(|results| results.get("result")) {
+        match serde_json::from_value(result.clone()) {
+          Ok(TweetResult::Tweet(tweet)) | Ok(TweetResult::TweetWithVisibilityResults { tweet }) => page.tweets.push(tweet),
+          _ => {}
+        }
+        return;
+      }
+      if object.get("cursorType").and_then(Value::as_str) == Some("Bottom") {
+        page.cursor = object.get("value").and_then(Value::as_str).map(|cursor| cursor.to_string());
+        return;
+      }
+
+      object.values().for_each(|value| collect_timeline(value, page));
+    }
+    Value::Array(values) => values.iter().for_each(|value| collect_timeline(value, page)),
+    _ => {}
+  }
+}
+
+//one guest token is shared by every request until the api rejects it
+async fn guest_token(ctx: &DownloadContext) -> Result<String, DownloaderError> {
+  let mut cached = ctx.guest_token.lock().await;
+  if let Some(guest_token) = cached.as_ref() {
+    return Ok(guest_token.clone());
+  }
+
+  let guest_token = fetch_guest_token(ctx).await?;
+  *cached = Some(guest_token.clone());
+  Ok(guest_token)
+}
+
+//another request may already have replaced the rejected token
+async fn expire_guest_token(ctx: &DownloadContext, guest_token: &str) {
+  let mut cached = ctx.guest_token.lock().await;
+  if cached.as_deref() == Some(guest_token) {
+    *cached = None;
+  }
+}
+
+async fn fetch_guest_token(ctx: &DownloadContext) -> Result<String, DownloaderError> {
+  let response = ctx
+    .client
+    .post(GUEST_TOKEN_URL)
+    .headers(api_headers(None, None)?)
+    .headers(ctx.headers.clone())
+    .send()
+    .await
+    .map_err(|_| DownloaderError::FetchError)?;
+
+  let body = response.text().await.map_err(|_| DownloaderError::FetchError)?;
+  let guest_token: GuestTokenResponse = serde_json::from_str(&body).map_err(|_| DownloaderError::FetchError)?;
+  Ok(guest_token.guest_token)
+}
+
+async fn graphql(ctx: &DownloadContext, url: &str, variables: &str) -> Result<String, DownloaderError> {
+  api_get(ctx, url, &[("variables", variables), ("features", FEATURES)]).await
+}
+
+//an expired guest token is answered with 401 or 403, the request is repeated once with a new one
+async fn api_get(ctx: &DownloadContext, url: &str, query: &[(&str, &str)]) -> Result<String, DownloaderError> {
+  //logged in requests are authenticated by the session cookies and their csrf token, a guest token would get them rejected
+  if let Some(csrf_token) = csrf_token(&ctx.cookie_jar, url) {
+    let response = ctx.get(url, api_headers(None, Some(&csrf_token))?).query(query).send().await.map_err(|_| DownloaderError::FetchError)?;
+    if !response.status().is_success() {
+      return Err(DownloaderError::FetchError);
+    }
+    return response.text().await.map_err(|_| DownloaderError::FetchError);
+  }
+
+  let mut retried = false;
+  loop {
+    let guest_token = guest_token(ctx).await?;
+    let response = ctx.get(url, api_headers(Some(&guest_token), None)?).query(query).send().await.map_err(|_| DownloaderError::FetchError)?;
+    match response.status() {
+      StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN if !retried => {
+        expire_guest_token(ctx, &guest_token).await;
+        retried = true;
+      }
+      status if status.is_success() => return response.text().await.map_err(|_| DownloaderError::FetchError),
+      _ => return Err(DownloaderError::FetchError),
+    }
+  }
+}
+
+//the ct0 cookie of a logged in session, sent back as the csrf token
+fn csrf_token(jar: &Jar, url: &str) -> Option<String> {
+  let cookies = jar.cookies(&Url::parse(url).ok()?)?;
+  cookies.to_str().ok()?.split(';').map(str::trim).find_map(|cookie| cookie.strip_prefix("ct0=")).map(|token| token.to_string())
+}
+
+fn api_headers(guest_token: Option<&str>, csrf_token: Option<&str>) -> Result<HeaderMap, DownloaderError> {
+  let mut headers = HeaderMap::new();
+  headers.insert(AUTHORIZATION, HeaderValue::from_static(BEARER_TOKEN));
+  if let Some(guest_token) = guest_token {
+    let guest_token = HeaderValue::from_str(guest_token).map_err(|_| DownloaderError::FetchError)?;
+    headers.insert(HeaderName::from_static("x-guest-token"), guest_token);
+  }
+  if let Some(csrf_token) = csrf_token {
+    let csrf_token = HeaderValue::from_str(csrf_token).map_err(|_| DownloaderError::FetchError)?;
+    headers.insert(HeaderName::from_static("x-csrf-token"), csrf_token);
+    headers.insert(HeaderName::from_static("x-twitter-auth-type"), HeaderValue::from_static("OAuth2Session"));
+  }
+
+  Ok(headers)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn fixture_tweet(json: &str) -> Tweet {
+    parse_tweet(json).ok().expect("fixture should parse")
+  }
+
+  #[test]
+  fn parse_tweet_reads_video_tweets() {
+    let tweet = fixture_tweet(include_str!("fixtures/twitter/tweet_video.json"));
+    assert_eq!(tweet.rest_id, "1790000000000000001");
+    assert_eq!(tweet.author_handle().as_deref(), Some("example"));
+    assert_eq!(tweet.author_name().as_deref(), Some("Example Author"));
+    assert_eq!(tweet.view_count(), Some(123456));
+    assert_eq!(tweet.legacy.favorite_count, Some(420));
+
+    let [media] = tweet.media() else { panic!("expected one media") };
+    assert!(media.is_video() && !media.is_gif() && !media.is_photo());
+  }
+
+  #[test]
+  fn mp4_variants_are_sorted_by_bitrate() {
+    let tweet = fixture_tweet(include_str!("fixtures/twitter/tweet_video.json"));
+    let variants = tweet.media()[0].mp4_variants();
+    let bitrates: Vec<Option<u64>> = variants.iter().map(|variant| variant.bitrate).collect();
+    assert_eq!(bitrates, [Some(2176000), Some(832000), Some(256000)]);
+    assert_eq!(variants[0].resolution().as_deref(), Some("1280x720"));
+    assert_eq!(tweet.media()[0].source_url(), Some(variants[0].url.clone()));
+  }
+
+  #[test]
+  fn hls_url_is_the_playlist_variant() {
+    let tweet = fixture_tweet(include_str!("fixtures/twitter/tweet_video.json"));
+    assert_eq!(tweet.media()[0].hls_url(), Some("https://video.twimg.com/ext_tw_video/1790000000000000100/pu/pl/playlist.m3u8?tag=12"));
+
+    let tweet = fixture_tweet(include_str!("fixtures/twitter/tweet_gif.json"));
+    assert_eq!(tweet.media()[0].hls_url(), None);
+  }
+
+  #[test]
+  fn parse_tweet_reads_gifs() {
+    let tweet = fixture_tweet(include_str!("fixtures/twitter/tweet_gif.json"));
+    let media = &tweet.media()[0];
+    assert!(media.is_gif() && media.is_video());
+    assert_eq!(media.source_url().as_deref(), Some("https://video.twimg.com/tweet_video/GabcDEF.mp4"));
+    assert_eq!(tweet.author_handle().as_deref(), Some("gifs"));
+  }
+
+  #[test]
+  fn parse_tweet_reads_photo_only_tweets() {
+    let tweet = fixture_tweet(include_str!("fixtures/twitter/tweet_photo.json"));
+    assert!(tweet.media().iter().all(|media| media.is_photo() && !media.is_video()));
+    let urls: Vec<String> = tweet.media().iter().map(Media::original_photo_url).collect();
+    assert_eq!(urls, ["https://pbs.twimg.com/media/GabcPhoto1?format=jpg&name=orig", "https://pbs.twimg.com/media/GabcPhoto2?format=png&name=orig"]);
+  }
+
+  #[test]
+  fn parse_tweet_unwraps_visibility_results() {
+    let tweet = fixture_tweet(include_str!("fixtures/twitter/tweet_with_visibility_results.json"));
+    assert_eq!(tweet.rest_id, "1790000000000000004");
+    let media = &tweet.media()[0];
+    assert!(media.mp4_variants().is_empty());
+    assert_eq!(media.source_url().as_deref(), Some("https://video.twimg.com/amplify_video/1790000000000000400/pl/playlist.m3u8"));
+  }
+
+  #[test]
+  fn parse_tweet_rejects_unavailable_tweets() {
+    assert!(matches!(parse_tweet(include_str!("fixtures/twitter/tweet_unavailable.json")), Err(DownloaderError::FetchError)));
+  }
+
+  #[test]
+  fn timestamp_applies_the_utc_offset() {
+    let timestamp = |json| fixture_tweet(json).timestamp();
+    assert_eq!(timestamp(include_str!("fixtures/twitter/tweet_video.json")), Some(1715610605));
+    assert_eq!(timestamp(include_str!("fixtures/twitter/tweet_gif.json")), Some(1735686000));
+    assert_eq!(timestamp(include_str!("fixtures/twitter/tweet_photo.json")), Some(1583020799));
+    assert_eq!(timestamp(include_str!("fixtures/twitter/tweet_with_visibility_results.json")), Some(1723728600));
+  }
+
+  #[test]
+  fn parse_timeline_collects_tweets_and_the_bottom_cursor() {
+    let page = parse_timeline(include_str!("fixtures/twitter/user_media.json")).ok().expect("fixture should parse");
+    let ids: Vec<&str> = page.tweets.iter().map(|tweet| tweet.rest_id.as_str()).collect();
+    assert_eq!(ids, ["1790000000000000011", "1790000000000000012"]);
+    assert_eq!(page.cursor.as_deref(), Some("DAABCgABGBOTTOM"));
+  }
+
+  #[test]
+  fn logged_in_requests_send_the_csrf_token_instead_of_a_guest_token() {
+    let jar = Jar::default();
+    assert_eq!(csrf_token(&jar, TWEET_RESULT_URL), None);
+
+    crate::cookies::add_domain_cookies(&jar, "auth_token=secret; ct0=csrf", "x.com");
+    assert_eq!(csrf_token(&jar, TWEET_RESULT_URL).as_deref(), Some("csrf"));
+
+    let headers = api_headers(None, Some("csrf")).ok().expect("token is a valid header");
+    assert_eq!(headers.get("x-csrf-token").and_then(|value| value.to_str().ok()), Some("csrf"));
+    assert!(headers.get("x-guest-token").is_none());
+  }
+}
@@ -0,0 +1,70 @@
+use std::collections::HashMap;
+use tokio::process::Command;
+
+use crate::{
+  dates,
+  downloader::{DownloadContext, PlatformDownloader},
+  downloader_error::DownloaderError,
+  format::Format,
+  info_json::{self, InfoJson},
+  platforms::{
+    twitter::TwitterDownloader,
+    twitter_api::{self, SpaceMetadata},
+  },
+};
+
+pub fn extract_space_id(url: &str) -> Option<String> {
+  let space_regex = regex::Regex::new(r"^https:\/\/(www\.)?(twitter|x)\.com\/i\/spaces\/(?P<id>\w+)").unwrap();
+  space_regex.captures(url).and_then(|captures| captures.name("id")).map(|id| id.as_str().to_string())
+}
+
+pub async fn list_formats(ctx: &DownloadContext, id: &str) -> Result<Vec<Format>, DownloaderError> {
+  let (_, stream_url) = resolve_space(ctx, id).await?;
+  Ok(vec![Format { id: "hls-audio".to_string(), resolution: None, url: stream_url }])
+}
+
+//the replay is an hls audio stream, ffmpeg remuxes it into m4a while tagging it
+pub async fn download(ctx: &DownloadContext, url: &str, id: &str, mut fields: HashMap<&str, String>) -> Result<Vec<String>, DownloaderError> {
+  let (space, stream_url) = resolve_space(ctx, id).await?;
+
+  let title = space.title.clone().unwrap_or_else(|| space.rest_id.clone());
+  let host = space.host().and_then(|host| host.screen_name());
+  let date = space.timestamp().map(dates::format_date);
+  fields.insert("name", title.clone());
+  fields.insert("ext", "m4a".to_string());
+  let output_name = ctx.output_path(&fields).await?;
+
+  let mut command = Command::new(&ctx.settings.ffmpeg_path);
+  command.args(["-i", &stream_url]).arg("-vn").args(["-c:a", "copy"]).args(["-metadata", &format!("title={title}")]);
+  if let Some(host) = &host {
+    command.args(["-metadata", &format!("artist={host}")]);
+  }
+  if let Some(date) = &date {
+    command.args(["-metadata", &format!("date={date}")]);
+  }
+  let output = command.arg("-y").arg(&output_name).output().await.map_err(|_| DownloaderError::FfmpegError)?;
+  if !output.status.success() {
+    return Err(DownloaderError::FfmpegError);
+  }
+
+  if ctx.settings.write_info_json {
+    let info = InfoJson {
+      text: Some(title),
+      uploader: host,
+      uploader_name: space.host().and_then(|host| host.name()),
+      timestamp: space.timestamp(),
+      media_urls: vec![stream_url.clone()],
+      format: Some(Format { id: "hls-audio".to_string(), resolution: None, url: stream_url }),
+      ..InfoJson::new(TwitterDownloader::NAME, Some(space.rest_id.clone()), url)
+    };
+    info_json::write(&output_name, &info).await?;
+  }
+
+  Ok(vec![output_name])
+}
+
+async fn resolve_space(ctx: &DownloadContext, id: &str) -> Result<(SpaceMetadata, String), DownloaderError> {
+  let space = twitter_api::fetch_space(ctx, id).await?;
+  let stream_url = twitter_api::fetch_space_stream_url(ctx, &space.media_key).await?;
+  Ok((space, stream_url))
+}
@@ -1,13 +1,14 @@
 use crate::downloader_error::DownloaderError;
+use std::collections::HashMap;
 use tokio::process::Command;
 
-use crate::playlist::media_playlist::MediaPlaylist;
+use crate::{downloader::DownloadContext, filename, playlist::media_playlist::MediaPlaylist};
 
 pub struct MasterPlaylist {
   pub resolution: String,
   video_media_playlist: Option<MediaPlaylist>,
   audio_media_playlist: Option<MediaPlaylist>,
-  video_media_url: String,
+  pub video_media_url: String,
   audio_media_url: Option<String>,
 }
 
@@ -22,28 +23,31 @@ impl MasterPlaylist {
     })
   }
 
-  pub async fn download(&mut self) -> Result<String, DownloaderError> {
-    self.video_media_playlist = Some(MediaPlaylist::from_url(&self.video_media_url).await?);
+  pub async fn download(&mut self, ctx: &DownloadContext, mut fields: HashMap<&str, String>) -> Result<String, DownloaderError> {
+    self.video_media_playlist = Some(MediaPlaylist::from_url(ctx, &self.video_media_url).await?);
     let video_media_playlist = self.video_media_playlist.as_ref().unwrap();
     let video_bytes = video_media_playlist.get_byte_data();
 
-    let video_name = video_media_playlist.name.split('/').last().unwrap().split('.').next().unwrap().to_string();
-    let output_name = format!("{}_{}.mp4", video_name, self.resolution);
+    let video_name = filename::sanitize(media_name(&video_media_playlist.name));
+    fields.insert("name", format!("{}_{}", video_name, self.resolution));
+    fields.insert("resolution", self.resolution.clone());
+    fields.insert("ext", "mp4".to_string());
+    let output_name = ctx.output_path(&fields).await?;
 
     tokio::fs::write(video_name.clone(), video_bytes).await.map_err(|_| DownloaderError::IOError)?;
 
     match &self.audio_media_url {
       Some(audio_media_url) => {
-        self.audio_media_playlist = Some(MediaPlaylist::from_url(&audio_media_url).await?);
+        self.audio_media_playlist = Some(MediaPlaylist::from_url(ctx, audio_media_url).await?);
         let audio_media_playlist = self.audio_media_playlist.as_ref().unwrap();
         let audio_bytes = audio_media_playlist.get_byte_data();
 
-        let audio_name = audio_media_playlist.name.split('/').last().unwrap().split('.').next().unwrap().to_string();
+        let audio_name = filename::sanitize(media_name(&audio_media_playlist.name));
         tokio::fs::write(audio_name.clone(), audio_bytes).await.map_err(|_| DownloaderError::IOError)?;
 
-        Command::new("ffmpeg")
-          .args(&["-i", &video_name])
-          .args(&["-i", &audio_name])
+        Command::new(&ctx.settings.ffmpeg_path)
+          .args(["-i", &video_name])
+          .args(["-i", &audio_name])
           .args(["-c", "copy"])
           .arg("-y")
           .arg(&output_name)
@@ -54,8 +58,8 @@ impl MasterPlaylist {
         tokio::fs::remove_file(audio_name).await.map_err(|_| DownloaderError::IOError)?;
       }
       None => {
-        Command::new("ffmpeg")
-          .args(&["-i", &video_name])
+        Command::new(&ctx.settings.ffmpeg_path)
+          .args(["-i", &video_name])
           .args(["-c", "copy"])
           .arg("-y")
           .arg(&output_name)
@@ -71,3 +75,8 @@ impl MasterPlaylist {
     Ok(output_name)
   }
 }
+
+fn media_name(segment_uri: &str) -> &str {
+  let file_name = segment_uri.split('/').next_back().unwrap_or_default();
+  file_name.split('.').next().unwrap_or_default()
+}
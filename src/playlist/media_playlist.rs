@@ -1,7 +1,8 @@
 use regex::Regex;
+use reqwest::header::HeaderMap;
 use std::sync::{Arc, Mutex};
 
-use crate::downloader_error::DownloaderError;
+use crate::{downloader::DownloadContext, downloader_error::DownloaderError};
 
 pub struct MediaPlaylist {
   pub name: String,
@@ -9,12 +10,12 @@ pub struct MediaPlaylist {
 }
 
 impl MediaPlaylist {
-  pub async fn from_url(url: &str) -> Result<Self, DownloaderError> {
+  pub async fn from_url(ctx: &DownloadContext, url: &str) -> Result<Self, DownloaderError> {
     let mut name = String::new();
     const BASE_URL: &str = "https://video.twimg.com";
 
     let response =
-      reqwest::get(url).await.map_err(|_| DownloaderError::FetchError)?.text().await.map_err(|_| DownloaderError::FetchError)?;
+      ctx.get(url, HeaderMap::new()).send().await.map_err(|_| DownloaderError::FetchError)?.text().await.map_err(|_| DownloaderError::FetchError)?;
     let lines = response.lines().filter(|&line| !line.is_empty()).collect::<Vec<&str>>();
 
     const BASE_SEGMENT_REGEX_STR: &str = r#"#EXT-X-MAP:URI="(?P<base_segment_url>.*)""#;
@@ -42,10 +43,9 @@ impl MediaPlaylist {
     let mut tasks = vec![];
     for (i, url) in ordered_urls.iter().enumerate() {
       let result_clone = ordered_bytes.clone();
-      let url_clone = url.clone();
+      let request = ctx.get(url, HeaderMap::new());
       tasks.push(tokio::spawn(async move {
-        let client = reqwest::Client::new();
-        if let Ok(response) = client.get(url_clone).send().await {
+        if let Ok(response) = request.send().await {
           if let Ok(bytes) = response.bytes().await {
             result_clone.lock().unwrap()[i] = bytes.to_vec();
           }
@@ -56,7 +56,7 @@ impl MediaPlaylist {
     let _ = futures::future::join_all(tasks).await;
     let bytes_data = ordered_bytes.lock().unwrap().iter().flatten().cloned().collect::<Vec<u8>>();
 
-    Ok(MediaPlaylist { name: name, byte_data: bytes_data })
+    Ok(MediaPlaylist { name, byte_data: bytes_data })
   }
 
   pub fn get_byte_data(&self) -> &Vec<u8> {
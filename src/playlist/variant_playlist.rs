@@ -1,25 +1,26 @@
 use futures::future::join_all;
 use regex::Regex;
+use reqwest::header::HeaderMap;
 use std::collections::HashMap;
 
-use crate::{downloader_error::DownloaderError, playlist::master_playlist::MasterPlaylist};
+use crate::{downloader::DownloadContext, downloader_error::DownloaderError, playlist::master_playlist::MasterPlaylist};
 
 pub struct VariantPlaylist {
   pub master_playlists: Vec<MasterPlaylist>,
 }
 
 impl VariantPlaylist {
-  pub async fn from_url(url: &str) -> Result<Self, DownloaderError> {
+  pub async fn from_url(ctx: &DownloadContext, url: &str) -> Result<Self, DownloaderError> {
     const BASE_URL: &str = "https://video.twimg.com";
 
     let response =
-      reqwest::get(url).await.map_err(|_| DownloaderError::FetchError)?.text().await.map_err(|_| DownloaderError::FetchError)?;
+      ctx.get(url, HeaderMap::new()).send().await.map_err(|_| DownloaderError::FetchError)?.text().await.map_err(|_| DownloaderError::FetchError)?;
 
     //audio pass
     const AUDIO_REGEX_STR: &str = r#"(?m)^#EXT-X-MEDIA:NAME="Audio".*GROUP-ID="(?P<audio_tag>audio-\d*)".*URI="(?P<audio_url>.*)""#;
 
     let mut audio_map = HashMap::<String, String>::new();
-    let audio_pass = Regex::new(&AUDIO_REGEX_STR).unwrap();
+    let audio_pass = Regex::new(AUDIO_REGEX_STR).unwrap();
     for capture in audio_pass.captures_iter(&response) {
       let Some(tag) = capture.name("audio_tag") else {
         continue;
@@ -31,7 +32,7 @@ impl VariantPlaylist {
     }
 
     //video pass
-    let contains_audio = audio_map.len() > 0;
+    let contains_audio = !audio_map.is_empty();
     let video_regex_str: &str = if contains_audio {
       r#"(?m)^#EXT-X-STREAM-INF.*RESOLUTION=(?P<video_resolution>\d*x\d*).*AUDIO="(?P<audio_match_tag>audio-\d*)"\n(?P<video_url>.*)"#
     } else {
@@ -87,6 +88,6 @@ impl VariantPlaylist {
       b_product.cmp(&a_product)
     });
 
-    Ok(VariantPlaylist { master_playlists: master_playlists })
+    Ok(VariantPlaylist { master_playlists })
   }
 }